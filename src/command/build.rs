@@ -3,9 +3,12 @@ mod dependencies;
 mod linking;
 
 use std::{
-    fs::{create_dir, create_dir_all, read, read_dir, remove_dir, remove_file},
+    fs::{
+        create_dir, create_dir_all, read, read_dir, read_to_string, remove_dir, remove_file, write,
+    },
     io::{stdout, Write},
     path::Path,
+    process::Command,
     time::Instant,
 };
 
@@ -17,21 +20,56 @@ use crossterm::{
 use hashbrown::HashMap;
 
 use crate::{
-    config::{hash::LoadHash, ProjectConfig},
-    file::{compile::compile, link::link, scan_dir},
+    config::{
+        error::ConfigError, hash::LoadHash, package::PackageConfig, platform::Platform,
+        ProjectConfig,
+    },
+    file::{compile::compile, link::link, scan_dir, Language},
 };
 
 use self::compiling::compiling;
 use self::dependencies::dependencies;
 use self::linking::linking;
 
-use super::{add_mode_path, get_project_path};
+pub use self::compiling::compile_flags;
+pub use self::linking::link_flags;
 
-#[derive(Clone, Copy)]
+use super::{get_cache_name, get_project_path};
+
+#[derive(Clone, Default)]
 pub struct BuildFlags {
     pub release: bool,
+    /// Target triple to build for instead of the host
+    pub target: Option<String>,
+    pub c_compiler: Option<String>,
+    pub cpp_compiler: Option<String>,
     pub rebuild: bool,
     pub pretty: bool,
+    pub target_cpu: Option<String>,
+    pub target_features: Vec<String>,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+}
+
+impl BuildFlags {
+    /// Platform to build for, the host or the target, with the profile, CPU and features of the
+    /// flags.
+    pub fn platform(&self) -> Result<Platform, String> {
+        let mut platform = self
+            .target
+            .as_deref()
+            .map_or_else(Platform::host, Platform::from_target);
+
+        if self.release {
+            platform.profile = "release".to_string();
+        }
+
+        platform.set_target_cpu(self.target_cpu.as_deref(), &self.target_features)?;
+        platform.user_features = self.features.iter().cloned().collect();
+        platform.default_features = !self.no_default_features;
+
+        Ok(platform)
+    }
 }
 
 pub fn build(
@@ -66,17 +104,15 @@ pub fn build(
         )?;
     }
 
-    let mut project_config = match ProjectConfig::load(project_config_path) {
-        Ok(project_config) => project_config,
-        Err(error) => {
-            ProjectConfig::handle_error(error, project_config_path)?;
+    let mut platform = flags.platform().map_err(ConfigError::Flag)?;
+    let mut project_config = ProjectConfig::load_for(project_config_path, &mut platform)?;
+
+    project_config.override_compilers(flags.c_compiler.as_deref(), flags.cpp_compiler.as_deref());
 
-            return Ok(true);
-        }
-    };
     let Some(mut package_config) = project_config.package.clone() else {
         return Ok(false);
     };
+    let cache_name = get_cache_name(&platform);
 
     let dir_path = project_path.join("./.maky");
     if !dir_path.is_dir() {
@@ -85,7 +121,7 @@ pub fn build(
 
     let binaries_dir_path = project_path.join(&package_config.binaries);
     if !binaries_dir_path.is_dir() {
-        create_dir(&binaries_dir_path)?;
+        create_dir_all(&binaries_dir_path)?;
     }
 
     for source in package_config.sources.iter() {
@@ -97,8 +133,7 @@ pub fn build(
         package_config.includes.push(source.clone());
     }
 
-    let objects_dir_path =
-        add_mode_path(&project_path.join(&package_config.objects), flags.release);
+    let objects_dir_path = project_path.join(&package_config.objects).join(&cache_name);
     if !objects_dir_path.is_dir() {
         create_dir_all(&objects_dir_path)?;
     }
@@ -111,19 +146,23 @@ pub fn build(
         .includes
         .push(Path::new(".maky/include").to_path_buf());
 
-    if cfg!(target_os = "linux") {
+    // The headers of the host don't fit a target, which has a sysroot for them instead
+    if platform.os == "linux" && !platform.cross {
         package_config.includes.push("/usr/include".into());
     }
 
     project_config.package = Some(package_config);
 
     let need_rebuild = dependencies(project_path, &mut project_config, flags, stderr)?;
+
+    write_package_header(project_path, project_config.package.as_ref().unwrap())?;
+
     let mut hash_hashmap = if flags.rebuild || need_rebuild {
         remove_objects(&objects_dir_path)?;
 
         HashMap::new()
     } else {
-        HashMap::load(project_path, flags.release).unwrap_or_default()
+        HashMap::load(project_path, &cache_name).unwrap_or_default()
     };
     let mut new_hash_hashmap = HashMap::new();
     let mut main_hashmap = HashMap::new();
@@ -150,7 +189,43 @@ pub fn build(
         }
     }
 
-    let project_config_hash = hash(&read(project_config_path)?);
+    // The extended, local and user configs, the compilers and the flags are part of the config
+    // hash so that changing them, the CPU or the environment recompiles everything
+    let package = project_config.package.as_ref().unwrap();
+    let project_config_hash = hash(
+        &[
+            read(project_config_path)?,
+            read(ProjectConfig::get_local_config_path(project_config_path)).unwrap_or_default(),
+            project_config
+                .extended_paths
+                .iter()
+                .flat_map(|extended_path| read(extended_path).unwrap_or_default())
+                .collect(),
+            ProjectConfig::get_user_config_path()
+                .and_then(|user_config_path| read(user_config_path).ok())
+                .unwrap_or_default(),
+            [&package.c_compiler, &package.cpp_compiler]
+                .into_iter()
+                .chain(
+                    compile_flags(package, &project_config.toolchain, &platform, Language::C)
+                        .iter()
+                        .map(|(flag, _)| flag),
+                )
+                .chain(
+                    compile_flags(package, &project_config.toolchain, &platform, Language::Cpp)
+                        .iter()
+                        .map(|(flag, _)| flag),
+                )
+                .chain(
+                    link_flags(&project_config.toolchain, &platform, flags.release)
+                        .iter()
+                        .map(|(flag, _)| flag),
+                )
+                .flat_map(|value| [value.as_bytes(), b"\0"].concat())
+                .collect(),
+        ]
+        .concat(),
+    );
 
     new_hash_hashmap.insert(project_config_path.to_owned(), project_config_hash);
 
@@ -179,16 +254,33 @@ pub fn build(
         &project_config,
         &files_to_compile,
         &mut new_hash_hashmap,
+        &platform,
         flags,
         stderr,
     )?;
 
+    // The binaries are shared by the feature sets, so switching to another one relinks everything
+    let mode = if flags.release { "release" } else { "debug" };
+    let mode = if platform.cross {
+        format!("{}-{mode}", platform.target)
+    } else {
+        mode.to_string()
+    };
+    let last_cache_name_path = project_path.join(format!(".maky/{mode}_cache"));
+    let is_switching_cache = read_to_string(&last_cache_name_path).unwrap_or(mode) != cache_name;
+
+    write(&last_cache_name_path, &cache_name)?;
+
     let files_to_link = link(
         project_path,
         &project_config,
         &main_hashmap,
         &lib_hashmap,
-        &files_to_compile,
+        if is_switching_cache {
+            &new_hash_hashmap
+        } else {
+            &files_to_compile
+        },
         &h_c_link,
         &c_h_link,
     )?;
@@ -199,6 +291,7 @@ pub fn build(
         &import_hashmap,
         &files_to_link,
         new_hash_hashmap,
+        &platform,
         flags,
         stderr,
     )?;
@@ -221,18 +314,45 @@ pub fn build(
     Ok(is_rebuilding)
 }
 
+/// Command of a compiler like `gcc` or `ccache gcc`, the words after the first being arguments.
+fn compiler_command(compiler: &str) -> Command {
+    let mut words = compiler.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or_default());
+
+    command.args(words);
+
+    command
+}
+
 fn remove_objects(objects_dir_path: &Path) -> anyhow::Result<()> {
-    for entry in read_dir(&objects_dir_path)? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-
-            if path.is_file() {
-                remove_file(path)?;
-            } else if path.is_dir() {
-                remove_dir(path)?;
-            }
+    for entry in read_dir(objects_dir_path)?.flatten() {
+        let path = entry.path();
+
+        if path.is_file() {
+            remove_file(path)?;
+        } else if path.is_dir() {
+            remove_dir(path)?;
         }
     }
 
     Ok(())
 }
+
+/// Write `maky/package.h`, defining the package metadata as `PACKAGE_NAME`, `PACKAGE_VERSION`...
+fn write_package_header(project_path: &Path, package_config: &PackageConfig) -> anyhow::Result<()> {
+    let header_path = project_path.join(".maky/include/maky/package.h");
+    let mut header = "#ifndef MAKY_PACKAGE_H\n#define MAKY_PACKAGE_H\n\n".to_string();
+
+    for (key, value) in package_config.get_metadata(project_path) {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+
+        header += &format!("#define PACKAGE_{} \"{value}\"\n", key.to_uppercase());
+    }
+
+    header += "\n#endif // MAKY_PACKAGE_H\n";
+
+    create_dir_all(header_path.parent().unwrap())?;
+    write(header_path, header)?;
+
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use std::{
+    env,
     io::{Read, Write},
     path::{Path, PathBuf},
-    process::{Child, Command, Stdio},
+    process::{Child, Stdio},
 };
 
 use blake3::Hash;
@@ -13,19 +14,103 @@ use hashbrown::HashMap;
 use kdam::{tqdm, BarExt, Column, RichProgress, Spinner};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{command::add_mode_path, config::ProjectConfig};
+use crate::{
+    command::get_cache_name,
+    config::{
+        package::PackageConfig, platform::Platform, toolchain::ToolchainConfig, ProjectConfig,
+    },
+    file::{get_language, Language},
+};
+
+use super::{compiler_command, BuildFlags};
+
+/// Flags given to the compiler of `language` besides the includes and files, with where each one
+/// comes from.
+pub fn compile_flags(
+    package: &PackageConfig,
+    toolchain: &ToolchainConfig,
+    platform: &Platform,
+    language: Language,
+) -> Vec<(String, String)> {
+    let mut flags = vec![
+        ("-fdiagnostics-color=always".to_string(), "maky".to_string()),
+        ("-fpic".to_string(), "maky".to_string()),
+    ];
+
+    if let Some(standard) = package.standard.as_ref() {
+        flags.push((format!("-std={standard}"), "package.standard".to_string()));
+    }
+
+    if platform.profile != "release" {
+        flags.extend(
+            ["-O0", "-g", "-Wall"].map(|flag| (flag.to_string(), "profile.dev".to_string())),
+        );
+    } else {
+        flags.push(("-O2".to_string(), "profile.release".to_string()));
+    }
+
+    if let Some(sysroot) = toolchain.sysroot.as_ref() {
+        flags.push((
+            format!("--sysroot={}", sysroot.to_string_lossy()),
+            "toolchain.sysroot".to_string(),
+        ));
+    }
+
+    if let Some(cpu) = platform.cpu.as_ref() {
+        flags.push((format!("-march={cpu}"), "--target-cpu".to_string()));
+    }
+
+    let mut features: Vec<&String> = platform.user_features.iter().collect();
+
+    features.sort();
+
+    for feature in features {
+        let define = feature
+            .chars()
+            .map(|char| {
+                if char.is_ascii_alphanumeric() {
+                    char.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        flags.push((format!("-DMAKY_FEATURE_{define}"), "features".to_string()));
+    }
+
+    // The flags of the environment come last to override the ones of Maky
+    let variables: &[&str] = match language {
+        Language::C => &["CPPFLAGS", "CFLAGS"],
+        Language::Cpp => &["CPPFLAGS", "CXXFLAGS"],
+        Language::Other => &[],
+    };
+
+    for variable in variables.iter() {
+        let variable = platform.env_variable(variable);
+
+        if let Ok(value) = env::var(&variable) {
+            flags.extend(
+                value
+                    .split_whitespace()
+                    .map(|flag| (flag.to_string(), format!("env.{variable}"))),
+            );
+        }
+    }
 
-use super::BuildFlags;
+    flags
+}
 
 pub fn compiling(
     project_path: &Path,
     project_config: &ProjectConfig,
     files_to_compile: &HashMap<PathBuf, Hash>,
     new_hash_hashmap: &mut HashMap<PathBuf, Hash>,
+    platform: &Platform,
     flags: &BuildFlags,
     stderr: &mut impl Write,
 ) -> anyhow::Result<()> {
-    let mut compile_progress_bar_option = if flags.pretty && files_to_compile.len() > 0 {
+    let mut compile_progress_bar_option = if flags.pretty && !files_to_compile.is_empty() {
         let mut compile_progress_bar = RichProgress::new(
             tqdm!(total = files_to_compile.len()),
             vec![
@@ -65,24 +150,22 @@ pub fn compiling(
     let commands = files_to_compile
         .into_par_iter()
         .map(|(file, file_hash)| {
-            let mut command = Command::new(project_config.get_compiler(file).unwrap());
+            let mut command = compiler_command(&project_config.get_compiler(file).unwrap());
 
             command
                 .current_dir(project_path)
                 .stdout(Stdio::null())
                 .stderr(Stdio::piped())
-                .arg("-fdiagnostics-color=always")
-                .arg("-fpic");
-
-            if let Some(standard) = project_config.package.as_ref().unwrap().standard.as_ref() {
-                command.arg(format!("-std={standard}"));
-            }
-
-            if !flags.release {
-                command.arg("-O0").arg("-g").arg("-Wall");
-            } else {
-                command.arg("-O2");
-            }
+                .args(
+                    compile_flags(
+                        project_config.package.as_ref().unwrap(),
+                        &project_config.toolchain,
+                        platform,
+                        file.extension().map_or(Language::Other, get_language),
+                    )
+                    .into_iter()
+                    .map(|(flag, _)| flag),
+                );
 
             (
                 file,
@@ -92,11 +175,13 @@ pub fn compiling(
                     .arg(file.strip_prefix(project_path).unwrap())
                     .arg("-o")
                     .arg(
-                        add_mode_path(
-                            &project_config.package.as_ref().unwrap().objects,
-                            flags.release,
-                        )
-                        .join(file_hash.to_hex().as_str()),
+                        project_config
+                            .package
+                            .as_ref()
+                            .unwrap()
+                            .objects
+                            .join(get_cache_name(platform))
+                            .join(file_hash.to_hex().as_str()),
                     )
                     .spawn()
                     .unwrap(),
@@ -112,7 +197,7 @@ pub fn compiling(
                 "[bold blue]".to_string()
                     + &file
                         .strip_prefix(project_path)
-                        .unwrap_or(&file)
+                        .unwrap_or(file)
                         .to_string_lossy(),
             );
             compile_progress_bar.update(1)?;
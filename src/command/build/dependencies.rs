@@ -1,5 +1,4 @@
 use std::{
-    env,
     fs::{create_dir_all, hard_link, remove_dir_all, remove_file},
     io::{self, Write},
     path::{Path, PathBuf},
@@ -17,7 +16,7 @@ use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
     command::{add_mode_path, get_project_path},
-    config::{dependency::DependencyConfig, lib::LibConfig, ProjectConfig},
+    config::{dependency::DependencyConfig, error::ConfigError, lib::LibConfig, ProjectConfig},
     file::scan_dir_dependency,
 };
 
@@ -30,7 +29,7 @@ pub fn dependencies(
     stderr: &mut impl Write,
 ) -> anyhow::Result<bool> {
     let mut dependencies_progress_bar_option =
-        if flags.pretty && project_config.dependencies.len() > 0 {
+        if flags.pretty && !project_config.dependencies.is_empty() {
             let mut dependencies_progress_bar = RichProgress::new(
                 tqdm!(total = project_config.dependencies.len()),
                 vec![
@@ -60,16 +59,27 @@ pub fn dependencies(
 
     create_dir_all(&project_dependencies_path)?;
 
+    // Dependencies are built for the same target and CPU, with their own features
+    let dependency_flags = {
+        let mut flags = flags.clone();
+
+        flags.pretty = false;
+        flags.features.clear();
+        flags.no_default_features = false;
+        flags
+    };
     let commands = project_config
         .dependencies
         .par_iter()
         .map(|(dependency_name, dependency_config)| {
             let (version, dependency_path) = match dependency_config {
-                DependencyConfig::Local { version, path } => (version, project_path.join(path)),
-                DependencyConfig::Git { version, git, rev } => {
+                DependencyConfig::Local { version, path, .. } => (version, project_path.join(path)),
+                DependencyConfig::Git {
+                    version, git, rev, ..
+                } => {
                     let mut git_errors = Vec::new();
                     let git_url = GitUrl::parse(git)
-                        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+                        .map_err(io::Error::other)?;
                     let project_dependency_path = project_dependencies_path.join(&git_url.name);
 
                     if !project_dependency_path.is_dir() {
@@ -83,7 +93,7 @@ pub fn dependencies(
                         project_dependency_path: &Path,
                         rev: &Option<String>,
                     ) -> Result<(), Error> {
-                        let repository = Repository::open(&project_dependency_path)?;
+                        let repository = Repository::open(project_dependency_path)?;
                         let mut remote = repository.find_remote("origin")?;
 
                         remote.fetch(&[] as &[&str], None, None)?;
@@ -130,7 +140,7 @@ pub fn dependencies(
                     if !git_errors.is_empty() {
                         return Ok(Err((
                             dependency_name.clone(),
-                            format!("{}", git_errors.join("\n")),
+                            git_errors.join("\n"),
                         )));
                     }
 
@@ -140,31 +150,48 @@ pub fn dependencies(
 
             let (dependency_path, dependency_config_path) =
                 get_project_path(&dependency_path.to_string_lossy());
-            let dependency_config = ProjectConfig::load(&dependency_config_path)?;
+            let dependency_config = match dependency_flags
+                .platform()
+                .map_err(|error| error.to_string())
+                .and_then(|mut platform| {
+                    ProjectConfig::load_for(&dependency_config_path, &mut platform)
+                        .map_err(|error| error.to_string())
+                }) {
+                Ok(dependency_config) => dependency_config,
+                Err(error) => return Ok(Err((dependency_name.clone(), error))),
+            };
+            let Some(dependency_package) = &dependency_config.package else {
+                let error = ConfigError::MissingPackage {
+                    path: dependency_config_path,
+                };
+
+                return Ok(Err((dependency_name.clone(), error.to_string())));
+            };
 
             if let Some(version_req) = version {
-                if !version_req.matches(&dependency_config.package.as_ref().unwrap().version) {
+                if !version_req.matches(&dependency_package.version) {
                     return Ok(Err((
                         dependency_name.clone(),
                         format!(
                             "version not matching, current is {} and required is {version_req}",
-                            dependency_config.package.as_ref().unwrap().version
+                            dependency_package.version
                         ),
                     )));
                 }
             }
 
             let mut stderr_buffer = Vec::new();
-            let has_rebuild = build(
+            let has_rebuild = match build(
                 dependency_path.to_string_lossy().to_string(),
-                &{
-                    let mut flags = flags.clone();
-
-                    flags.pretty = false;
-                    flags
-                },
+                &dependency_flags,
                 &mut stderr_buffer,
-            )?;
+            ) {
+                Ok(has_rebuild) => has_rebuild,
+                Err(error) => match error.downcast::<ConfigError>() {
+                    Ok(error) => return Ok(Err((dependency_name.clone(), error.to_string()))),
+                    Err(error) => return Err(error),
+                },
+            };
 
             if !stderr_buffer.is_empty() {
                 return Ok(Err((
@@ -183,16 +210,25 @@ pub fn dependencies(
         flags.release,
     );
     let project_binaries_path = project_path.join(&binaries_path);
+    let library_prefix = project_config
+        .toolchain
+        .library_prefix
+        .clone()
+        .unwrap_or_default();
+    let library_suffix = format!(
+        ".{}",
+        project_config
+            .toolchain
+            .library_extension
+            .as_deref()
+            .unwrap_or_default()
+    );
 
     remove_dir_all(project_path.join(".maky/include")).ok();
 
-    let has_rebuild = commands.iter().any(|command| {
-        if let Ok(Ok((_, _, _, true))) = command {
-            true
-        } else {
-            false
-        }
-    });
+    let has_rebuild = commands
+        .iter()
+        .any(|command| matches!(command, Ok(Ok((_, _, _, true)))));
 
     for command in commands.into_iter() {
         let (dependency_name, dependency_path, mut dependency_config, _) = match command? {
@@ -205,18 +241,18 @@ pub fn dependencies(
 
         if let Some(dependencies_progress_bar) = &mut dependencies_progress_bar_option {
             dependencies_progress_bar.columns[2] =
-                Column::Text("[bold blue]".to_string() + &dependency_name);
+                Column::Text("[bold blue]".to_string() + dependency_name);
             dependencies_progress_bar.update(1)?;
         }
 
-        let project_include_path = project_path
-            .join(".maky/include/deps")
-            .join(dependency_name);
+        let package = dependency_config.package.as_mut().unwrap();
+        // The headers and libraries are imported by the name of the package, or by the key of the
+        // dependency when it has none
+        let package_name = package.name.clone().unwrap_or(dependency_name.clone());
+        let project_include_path = project_path.join(".maky/include/deps").join(&package_name);
 
         create_dir_all(&project_include_path)?;
 
-        let package = dependency_config.package.as_mut().unwrap();
-
         package.includes.extend_from_slice(&package.sources);
 
         for (_, library) in dependency_config.libraries.into_iter() {
@@ -236,40 +272,38 @@ pub fn dependencies(
             }
         }
 
-        for entry in
-            add_mode_path(&dependency_path.join(&package.binaries), flags.release).read_dir()?
+        for entry in add_mode_path(&dependency_path.join(&package.binaries), flags.release)
+            .read_dir()?
+            .flatten()
         {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-
-                if let Some(true) = path.file_name().map(|file_name| {
-                    file_name
-                        .to_string_lossy()
-                        .contains(env::consts::DLL_SUFFIX)
-                }) {
-                    create_dir_all(&project_binaries_path)?;
-
-                    let link = project_binaries_path.join(path.file_name().unwrap());
-
-                    remove_file(&link).ok();
-                    hard_link(&path, link)?;
-
-                    let lib_name = path.file_stem().unwrap().to_string_lossy();
-                    let lib_name = lib_name.strip_prefix("lib").unwrap_or(&lib_name);
-                    let lib_config = LibConfig {
-                        library: vec![lib_name.to_string()],
-                        directories: vec![binaries_path.clone()],
-                        includes: Vec::new(),
-                        pkg_config: HashMap::new(),
-                    };
-                    let mut lib_name_split: Vec<&str> = lib_name.split("_").collect();
-
-                    lib_name_split.pop();
-                    project_config.libraries.insert(
-                        format!("{dependency_name}/{}", lib_name_split.join("_")),
-                        lib_config,
-                    );
-                }
+            let path = entry.path();
+
+            if let Some(true) = path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().contains(&library_suffix))
+            {
+                create_dir_all(&project_binaries_path)?;
+
+                let link = project_binaries_path.join(path.file_name().unwrap());
+
+                remove_file(&link).ok();
+                hard_link(&path, link)?;
+
+                let lib_name = path.file_stem().unwrap().to_string_lossy();
+                let lib_name = lib_name.strip_prefix(&library_prefix).unwrap_or(&lib_name);
+                let lib_config = LibConfig {
+                    library: vec![lib_name.to_string()],
+                    directories: vec![binaries_path.clone()],
+                    includes: Vec::new(),
+                    pkg_config: HashMap::new(),
+                };
+                let mut lib_name_split: Vec<&str> = lib_name.split("_").collect();
+
+                lib_name_split.pop();
+                project_config.libraries.insert(
+                    format!("{package_name}/{}", lib_name_split.join("_")),
+                    lib_config,
+                );
             }
         }
     }
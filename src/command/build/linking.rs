@@ -1,9 +1,9 @@
 use std::{
     env,
-    fs::create_dir_all,
+    fs::{create_dir_all, remove_file},
     io::{Read, Write},
     path::{Path, PathBuf},
-    process::{Child, Command, Stdio},
+    process::{Child, Stdio},
 };
 
 use aho_corasick::AhoCorasick;
@@ -12,27 +12,64 @@ use crossterm::{
     execute,
     style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
 };
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 use kdam::{tqdm, BarExt, Column, RichProgress, Spinner};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use crate::{
-    command::add_mode_path,
-    config::{hash::SaveHash, ProjectConfig},
+    command::{add_mode_path, get_cache_name},
+    config::{hash::SaveHash, platform::Platform, toolchain::ToolchainConfig, ProjectConfig},
+    file::link::FileToLink,
 };
 
-use super::BuildFlags;
+use super::{compiler_command, BuildFlags};
+
+/// Flags given to the linker besides the libraries and files, with where each one comes from.
+pub fn link_flags(
+    toolchain: &ToolchainConfig,
+    platform: &Platform,
+    release: bool,
+) -> Vec<(String, String)> {
+    let mut flags = vec![("-fdiagnostics-color=always".to_string(), "maky".to_string())];
+
+    if let Some(sysroot) = toolchain.sysroot.as_ref() {
+        flags.push((
+            format!("--sysroot={}", sysroot.to_string_lossy()),
+            "toolchain.sysroot".to_string(),
+        ));
+    }
+
+    if !release {
+        flags.extend(["-g", "-Wall"].map(|flag| (flag.to_string(), "profile.dev".to_string())));
+    } else {
+        flags.push(("-s".to_string(), "profile.release".to_string()));
+    }
+
+    let variable = platform.env_variable("LDFLAGS");
+
+    if let Ok(value) = env::var(&variable) {
+        flags.extend(
+            value
+                .split_whitespace()
+                .map(|flag| (flag.to_string(), format!("env.{variable}"))),
+        );
+    }
+
+    flags
+}
 
+#[allow(clippy::too_many_arguments)]
 pub fn linking(
     project_path: &Path,
     project_config: &ProjectConfig,
     import_hashmap: &HashMap<PathBuf, Vec<String>>,
-    files_to_link: &Vec<(PathBuf, bool, Option<String>, HashSet<PathBuf>)>,
+    files_to_link: &Vec<FileToLink>,
     mut new_hash_hashmap: HashMap<PathBuf, Hash>,
+    platform: &Platform,
     flags: &BuildFlags,
     stderr: &mut impl Write,
 ) -> anyhow::Result<()> {
-    let mut link_progress_bar_option = if flags.pretty && files_to_link.len() > 0 {
+    let mut link_progress_bar_option = if flags.pretty && !files_to_link.is_empty() {
         let mut link_progress_bar = RichProgress::new(
             tqdm!(total = files_to_link.len()),
             vec![
@@ -66,7 +103,7 @@ pub fn linking(
 
             args.push("-L".to_string());
 
-            if let Some(directory) = lib_config.directories.get(0) {
+            if let Some(directory) = lib_config.directories.first() {
                 args.extend([
                     directory.to_string_lossy().to_string(),
                     "-Wl,-rpath".to_string(),
@@ -80,7 +117,7 @@ pub fn linking(
                 }
             }
 
-            if cfg!(target_os = "linux") {
+            if platform.os == "linux" && !platform.cross {
                 args.extend([
                     "/usr/local/lib/".to_string(),
                     "-Wl,-rpath".to_string(),
@@ -103,22 +140,21 @@ pub fn linking(
         libraries_args
     };
 
+    let cache_name = get_cache_name(platform);
     let commands = files_to_link
         .into_par_iter()
         .map(|(file, is_library, name_option, file_to_link)| {
-            let mut command = Command::new(project_config.get_compiler(file).unwrap());
+            let mut command = compiler_command(&project_config.get_compiler(file).unwrap());
 
             command
                 .current_dir(project_path)
                 .stdout(Stdio::null())
                 .stderr(Stdio::piped())
-                .arg("-fdiagnostics-color=always");
-
-            if !flags.release {
-                command.arg("-g").arg("-Wall");
-            } else {
-                command.arg("-s");
-            }
+                .args(
+                    link_flags(&project_config.toolchain, platform, flags.release)
+                        .into_iter()
+                        .map(|(flag, _)| flag),
+                );
 
             if *is_library {
                 command.arg("--shared");
@@ -129,8 +165,10 @@ pub fn linking(
 
             for c_file in file_to_link {
                 if let Some(hash) = new_hash_hashmap.get(c_file) {
-                    let o_file =
-                        add_mode_path(&package.objects, flags.release).join(hash.to_hex().as_str());
+                    let o_file = package
+                        .objects
+                        .join(&cache_name)
+                        .join(hash.to_hex().as_str());
 
                     command.arg(&o_file);
                     o_c_link.push((
@@ -158,32 +196,65 @@ pub fn linking(
                 .clone()
                 .unwrap_or(file.file_stem().unwrap().to_string_lossy().to_string());
 
+            let toolchain = &project_config.toolchain;
+
             if *is_library {
                 output_file = output_path.join(format!(
                     "{}{name}_{}.{}",
-                    env::consts::DLL_PREFIX,
+                    toolchain.library_prefix.as_deref().unwrap_or_default(),
                     package.version,
-                    env::consts::DLL_EXTENSION
+                    toolchain.library_extension.as_deref().unwrap_or_default()
                 ));
             } else {
-                output_file = output_path.join(name);
-                output_file.set_extension(env::consts::EXE_EXTENSION);
+                output_file = output_path.join(&name);
+                output_file.set_extension(
+                    toolchain
+                        .executable_extension
+                        .as_deref()
+                        .unwrap_or_default(),
+                );
             }
 
-            create_dir_all(project_path.join(output_path))?;
+            create_dir_all(project_path.join(&output_path))?;
+
+            // The objects of a library are archived too, for the projects linking it statically
+            let archive = if *is_library {
+                let archive_file = output_path.join(format!(
+                    "{}{name}_{}.a",
+                    toolchain.library_prefix.as_deref().unwrap_or_default(),
+                    package.version,
+                ));
+                let mut archive = compiler_command(toolchain.archiver.as_deref().unwrap_or("ar"));
+
+                // Archiving adds to the members of an existing archive
+                remove_file(project_path.join(&archive_file)).ok();
+
+                archive
+                    .current_dir(project_path)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .arg("rcs")
+                    .arg(archive_file)
+                    .args(o_c_link.iter().map(|(o_file, _)| o_file));
+
+                Some(archive.spawn().unwrap())
+            } else {
+                None
+            };
 
             Ok(Some((
                 file,
                 command.arg("-o").arg(output_file).spawn().unwrap(),
+                archive,
                 o_c_link,
             )))
         })
-        .collect::<Vec<anyhow::Result<Option<(&PathBuf, Child, Vec<(String, String)>)>>>>();
+        .collect::<Vec<anyhow::Result<Option<(&PathBuf, Child, Option<Child>, Vec<(String, String)>)>>>>();
 
     let mut errors = Vec::new();
 
     for command in commands.into_iter() {
-        if let Some((file, mut command, o_c_link)) = command? {
+        if let Some((file, command, archive, o_c_link)) = command? {
             if let Some(link_progress_bar) = &mut link_progress_bar_option {
                 link_progress_bar.columns[2] = Column::Text(
                     "[bold blue]".to_string()
@@ -195,24 +266,23 @@ pub fn linking(
                 link_progress_bar.update(1)?;
             }
 
-            if let Ok(exit_code) = command.wait() {
-                if !exit_code.success() {
+            let mut buffer = String::new();
+
+            for mut child in [Some(command), archive].into_iter().flatten() {
+                if !child.wait().is_ok_and(|exit_code| exit_code.success()) {
                     new_hash_hashmap.remove(file);
                 }
 
-                let mut buffer = String::new();
-
-                if let Some(stderr) = command.stderr.as_mut() {
+                if let Some(stderr) = child.stderr.as_mut() {
                     stderr.read_to_string(&mut buffer)?;
-                    errors.push((file, buffer, o_c_link));
                 }
-            } else {
-                new_hash_hashmap.remove(file);
             }
+
+            errors.push((file, buffer, o_c_link));
         }
     }
 
-    new_hash_hashmap.save(project_path, flags.release)?;
+    new_hash_hashmap.save(project_path, &cache_name)?;
 
     if let Some(link_progress_bar) = &mut link_progress_bar_option {
         link_progress_bar.columns.drain(1..6);
@@ -42,6 +42,15 @@ pub fn clean(config_file: String) -> anyhow::Result<()> {
                     }
                 }
 
+                for target_config in project_config.target.values() {
+                    if let Some(binaries) = &target_config.binaries {
+                        remove_dir_all(project_path.join(binaries)).ok();
+                    }
+                    if let Some(objects) = &target_config.objects {
+                        remove_dir_all(project_path.join(objects)).ok();
+                    }
+                }
+
                 for dependency_config in project_config.dependencies.values() {
                     let DependencyConfig::Local { path, .. } = dependency_config else {
                         continue;
@@ -51,7 +60,7 @@ pub fn clean(config_file: String) -> anyhow::Result<()> {
                 }
             }
         }
-        Err(error) => ProjectConfig::handle_error(error, project_config_path)?,
+        Err(error) => return Err(error.into()),
     }
 
     Ok(())
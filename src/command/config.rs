@@ -0,0 +1,426 @@
+use std::io::{stdout, Write};
+
+use clap::ValueEnum;
+use serde_json::{json, Map};
+
+use crate::{
+    config::{error::ConfigError, toolchain::ToolchainConfig, ProjectConfig},
+    file::Language,
+};
+
+use super::{compile_flags, get_project_path, link_flags, BuildFlags};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Dev,
+    Release,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+/// Resolved value with the section it comes from, or the values of a list with theirs.
+enum Value {
+    Single(String, String),
+    List(Vec<(String, String)>),
+}
+
+struct Section {
+    name: String,
+    origin: Option<String>,
+    values: Vec<(&'static str, Value)>,
+}
+
+/// Print the config Maky builds with once the specific configs are merged, with where each value
+/// comes from.
+pub fn config_show(
+    config_file: String,
+    flags: &BuildFlags,
+    format: ConfigFormat,
+) -> anyhow::Result<()> {
+    let (project_path, project_config_path) = &get_project_path(&config_file);
+    let mut platform = flags.platform().map_err(ConfigError::Flag)?;
+    let mut project_config = ProjectConfig::load_for(project_config_path, &mut platform)?;
+
+    project_config.override_compilers(flags.c_compiler.as_deref(), flags.cpp_compiler.as_deref());
+
+    let Some(package) = &project_config.package else {
+        return Ok(());
+    };
+    let origin = |key: &str, index: usize| {
+        project_config
+            .origins
+            .get(key)
+            .and_then(|origins| origins.get(index))
+            .cloned()
+            .unwrap_or("package".to_string())
+    };
+    let single = |key: &str, value: String| Value::Single(value, origin(key, 0));
+    let list = |key: &str, values: Vec<String>| {
+        Value::List(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| (value, origin(key, index)))
+                .collect(),
+        )
+    };
+    let platform_origin = if platform.cross { "--target" } else { "host" };
+    let features_origin = if flags.target_cpu.is_some() || !flags.target_features.is_empty() {
+        "--target-cpu"
+    } else {
+        platform_origin
+    };
+    let mut features: Vec<String> = platform
+        .features
+        .iter()
+        .map(|feature| feature.to_string())
+        .collect();
+
+    features.sort();
+
+    let mut user_features: Vec<String> = platform.user_features.iter().cloned().collect();
+
+    user_features.sort();
+
+    let mut sections = vec![Section {
+        name: "platform".to_string(),
+        origin: None,
+        values: vec![
+            (
+                "target",
+                Value::Single(platform.target.clone(), platform_origin.to_string()),
+            ),
+            (
+                "os",
+                Value::Single(platform.os.clone(), platform_origin.to_string()),
+            ),
+            (
+                "family",
+                Value::Single(platform.family.clone(), platform_origin.to_string()),
+            ),
+            (
+                "arch",
+                Value::Single(platform.arch.clone(), platform_origin.to_string()),
+            ),
+            (
+                "env",
+                Value::Single(platform.env.clone(), platform_origin.to_string()),
+            ),
+            (
+                "profile",
+                Value::Single(platform.profile.clone(), "--profile".to_string()),
+            ),
+            (
+                "features",
+                Value::List(
+                    features
+                        .into_iter()
+                        .map(|feature| (feature, features_origin.to_string()))
+                        .collect(),
+                ),
+            ),
+            (
+                "user-features",
+                Value::List(
+                    user_features
+                        .into_iter()
+                        .map(|feature| {
+                            let origin = if flags.features.contains(&feature) {
+                                "--features"
+                            } else {
+                                "features"
+                            };
+
+                            (feature, origin.to_string())
+                        })
+                        .collect(),
+                ),
+            ),
+        ],
+    }];
+    let mut package_values = vec![
+        (
+            "name",
+            Value::Single(
+                package.get_name(project_path),
+                if package.name.is_some() {
+                    "package"
+                } else {
+                    "default"
+                }
+                .to_string(),
+            ),
+        ),
+        (
+            "version",
+            Value::Single(package.version.to_string(), "package".to_string()),
+        ),
+        (
+            "c-compiler",
+            single("package.c-compiler", package.c_compiler.clone()),
+        ),
+        (
+            "cpp-compiler",
+            single("package.cpp-compiler", package.cpp_compiler.clone()),
+        ),
+    ];
+
+    if let Some(standard) = &package.standard {
+        package_values.push((
+            "standard",
+            Value::Single(standard.clone(), "package".to_string()),
+        ));
+    }
+
+    package_values.extend([
+        (
+            "binaries",
+            single(
+                "package.binaries",
+                package.binaries.to_string_lossy().to_string(),
+            ),
+        ),
+        (
+            "objects",
+            single(
+                "package.objects",
+                package.objects.to_string_lossy().to_string(),
+            ),
+        ),
+        (
+            "sources",
+            list(
+                "package.sources",
+                package
+                    .sources
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect(),
+            ),
+        ),
+        (
+            "includes",
+            list(
+                "package.includes",
+                package
+                    .includes
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect(),
+            ),
+        ),
+    ]);
+    sections.push(Section {
+        name: "package".to_string(),
+        origin: None,
+        values: package_values,
+    });
+
+    let mut library_names: Vec<&String> = project_config.libraries.keys().collect();
+
+    library_names.sort();
+
+    for library_name in library_names {
+        let library = &project_config.libraries[library_name];
+        let library_origin = origin(&format!("libraries.{library_name}"), 0);
+        let library_list = |key: &str, values: Vec<String>| {
+            let key = format!("libraries.{library_name}.{key}");
+
+            if project_config.origins.contains_key(&key) {
+                list(&key, values)
+            } else {
+                Value::List(
+                    values
+                        .into_iter()
+                        .map(|value| (value, library_origin.clone()))
+                        .collect(),
+                )
+            }
+        };
+
+        sections.push(Section {
+            name: format!("libraries.{}", toml_key(library_name)),
+            origin: Some(library_origin.clone()),
+            values: vec![
+                ("library", library_list("library", library.library.clone())),
+                (
+                    "directories",
+                    library_list(
+                        "directories",
+                        library
+                            .directories
+                            .iter()
+                            .map(|path| path.to_string_lossy().to_string())
+                            .collect(),
+                    ),
+                ),
+                (
+                    "includes",
+                    library_list(
+                        "includes",
+                        library
+                            .includes
+                            .iter()
+                            .map(|path| path.to_string_lossy().to_string())
+                            .collect(),
+                    ),
+                ),
+            ],
+        });
+    }
+
+    sections.push(Section {
+        name: "toolchain".to_string(),
+        origin: None,
+        values: ToolchainConfig::KEYS
+            .into_iter()
+            .filter_map(|key| {
+                project_config
+                    .toolchain
+                    .get(key)
+                    .map(|value| (key, single(&format!("toolchain.{key}"), value)))
+            })
+            .collect(),
+    });
+
+    sections.push(Section {
+        name: "flags".to_string(),
+        origin: None,
+        values: vec![
+            (
+                "c",
+                Value::List(compile_flags(
+                    package,
+                    &project_config.toolchain,
+                    &platform,
+                    Language::C,
+                )),
+            ),
+            (
+                "cpp",
+                Value::List(compile_flags(
+                    package,
+                    &project_config.toolchain,
+                    &platform,
+                    Language::Cpp,
+                )),
+            ),
+            (
+                "link",
+                Value::List(link_flags(
+                    &project_config.toolchain,
+                    &platform,
+                    flags.release,
+                )),
+            ),
+        ],
+    });
+
+    let output = match format {
+        ConfigFormat::Toml => to_toml(&sections),
+        ConfigFormat::Json => to_json(&sections)?,
+    };
+
+    write!(stdout(), "{output}")?;
+
+    Ok(())
+}
+
+/// Quote a key when it isn't a bare TOML key.
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+    {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+/// TOML with the origin of each value in a comment.
+fn to_toml(sections: &[Section]) -> String {
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let mut toml = String::new();
+
+    for section in sections.iter() {
+        if !toml.is_empty() {
+            toml.push('\n');
+        }
+
+        toml += &format!("[{}]", section.name);
+
+        if let Some(origin) = &section.origin {
+            toml += &format!(" # {origin}");
+        }
+
+        toml.push('\n');
+
+        for (key, value) in section.values.iter() {
+            match value {
+                Value::Single(value, origin) => {
+                    toml += &format!("{key} = {} # {origin}\n", quote(value))
+                }
+                Value::List(values) if values.is_empty() => toml += &format!("{key} = []\n"),
+                Value::List(values) => {
+                    toml += &format!("{key} = [\n");
+
+                    for (value, origin) in values.iter() {
+                        toml += &format!("    {}, # {origin}\n", quote(value));
+                    }
+
+                    toml += "]\n";
+                }
+            }
+        }
+    }
+
+    toml
+}
+
+/// JSON with each value as a `value` and `origin` object.
+fn to_json(sections: &[Section]) -> anyhow::Result<String> {
+    let mut root = Map::new();
+
+    for section in sections.iter() {
+        let mut object = Map::new();
+
+        if let Some(origin) = &section.origin {
+            object.insert("origin".to_string(), json!(origin));
+        }
+
+        for (key, value) in section.values.iter() {
+            let value = match value {
+                Value::Single(value, origin) => json!({ "value": value, "origin": origin }),
+                Value::List(values) => values
+                    .iter()
+                    .map(|(value, origin)| json!({ "value": value, "origin": origin }))
+                    .collect(),
+            };
+
+            object.insert(key.to_string(), value);
+        }
+
+        match section.name.split_once('.') {
+            Some((parent, name)) => {
+                let name = name.trim_matches('"').to_string();
+
+                root.entry(parent)
+                    .or_insert(json!({}))
+                    .as_object_mut()
+                    .unwrap()
+                    .insert(name, object.into());
+            }
+            None => {
+                root.insert(section.name.clone(), object.into());
+            }
+        }
+    }
+
+    Ok(serde_json::to_string_pretty(&root)? + "\n")
+}
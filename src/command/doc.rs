@@ -0,0 +1,843 @@
+use std::{
+    fmt,
+    fs::{create_dir_all, read_dir, read_to_string, write},
+    io::stderr,
+    mem::take,
+    path::PathBuf,
+};
+
+use crossterm::{
+    execute,
+    style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
+};
+use hashbrown::HashMap;
+use indoc::formatdoc;
+use rayon::prelude::*;
+
+use crate::{config::ProjectConfig, file::scan_dir_dependency};
+
+use super::{
+    format::lexer::{is_blank, Token, TokenArray, Tokenizer},
+    get_project_path,
+};
+
+const STYLE: &str = "body { max-width: 60rem; margin: auto; padding: 1rem; font-family: sans-serif; line-height: 1.5; } \
+    pre { padding: 0.75rem; overflow-x: auto; background: #f4f4f4; } \
+    section { margin-bottom: 2rem; } \
+    a { color: #2a6ebb; }";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Function,
+    Struct,
+    Class,
+    Union,
+    Enum,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ItemKind::Function => "function",
+            ItemKind::Struct => "struct",
+            ItemKind::Class => "class",
+            ItemKind::Union => "union",
+            ItemKind::Enum => "enum",
+        })
+    }
+}
+
+/// Function prototype or type definition of a header, with its doc comment.
+#[derive(Debug)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub name: String,
+    pub declaration: String,
+    pub doc: String,
+    /// Names used by the declaration, linked to their documentation when there is one
+    pub references: Vec<String>,
+}
+
+impl Item {
+    fn anchor(&self) -> String {
+        format!("{}.{}", self.kind, self.name)
+    }
+}
+
+struct Header {
+    path: PathBuf,
+    items: Vec<Item>,
+}
+
+struct Package {
+    name: String,
+    description: Option<String>,
+    is_dependency: bool,
+    headers: Vec<Header>,
+}
+
+/// Page and anchor documenting a name.
+struct Link {
+    package: String,
+    anchor: String,
+}
+
+impl Link {
+    fn href(&self, root: &str, extension: &str) -> String {
+        format!("{root}{}/index.{extension}#{}", self.package, self.anchor)
+    }
+}
+
+/// Generate the HTML and Markdown documentation of the project headers, and of the headers of its
+/// dependencies, in `.maky/doc`.
+pub fn doc(config_file: String) -> anyhow::Result<()> {
+    let (project_path, project_config_path) = &get_project_path(&config_file);
+    let project_config = ProjectConfig::load(project_config_path)?;
+    let Some(package) = &project_config.package else {
+        return Ok(());
+    };
+    let roots: Vec<PathBuf> = package
+        .includes
+        .iter()
+        .chain(package.sources.iter())
+        .map(|path| project_path.join(path))
+        .collect();
+    let mut packages = vec![load_package(package.get_name(project_path), &roots, false)];
+
+    packages[0].description = package.description.clone();
+
+    let dependencies_path = project_path.join(".maky/include/deps");
+
+    if let Ok(read_dir) = read_dir(&dependencies_path) {
+        let mut dependency_paths: Vec<PathBuf> = read_dir
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+
+        dependency_paths.sort();
+
+        // The folders are named like the dependencies are imported
+        for dependency_path in dependency_paths {
+            let name = dependency_path.file_name().unwrap().to_string_lossy();
+
+            packages.push(load_package(name.to_string(), &[dependency_path], true));
+        }
+    } else if !project_config.dependencies.is_empty() {
+        execute!(
+            stderr(),
+            SetForegroundColor(Color::DarkYellow),
+            Print("Warning ".bold()),
+            ResetColor,
+            Print("the dependencies are not documented until the project is built\n"),
+        )?;
+    }
+
+    // The project comes first, so its names are linked over the ones of its dependencies
+    let mut links = HashMap::new();
+
+    for package in packages.iter() {
+        for item in package
+            .headers
+            .iter()
+            .flat_map(|header| header.items.iter())
+        {
+            links.entry(item.name.clone()).or_insert_with(|| Link {
+                package: package.name.clone(),
+                anchor: item.anchor(),
+            });
+        }
+    }
+
+    let doc_path = project_path.join(".maky/doc");
+
+    for package in packages.iter() {
+        let package_path = doc_path.join(&package.name);
+
+        create_dir_all(&package_path)?;
+        write(
+            package_path.join("index.html"),
+            package_html(package, &links),
+        )?;
+        write(
+            package_path.join("index.md"),
+            package_markdown(package, &links),
+        )?;
+    }
+
+    write(doc_path.join("index.html"), index_html(&packages))?;
+    write(doc_path.join("index.md"), index_markdown(&packages))?;
+
+    execute!(
+        stderr(),
+        SetForegroundColor(Color::Green),
+        Print("Documented ".bold()),
+        ResetColor,
+        Print(format!(
+            "{} package(s) in {}\n",
+            packages.len(),
+            doc_path.join("index.html").to_string_lossy()
+        )),
+    )?;
+
+    Ok(())
+}
+
+fn load_package(name: String, roots: &[PathBuf], is_dependency: bool) -> Package {
+    let mut paths = Vec::new();
+
+    for root in roots.iter() {
+        if root.is_dir() {
+            paths.extend(scan_dir_dependency(root).unwrap_or_default());
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+
+    let mut headers: Vec<Header> = paths
+        .par_iter()
+        .filter_map(|path| {
+            // The deepest root is the one the header is included from
+            let relative_path = roots
+                .iter()
+                .filter_map(|root| path.strip_prefix(root).ok())
+                .min_by_key(|relative_path| relative_path.components().count())
+                .unwrap_or(path);
+            let code = read_to_string(path).ok()?;
+            let token_array = match Tokenizer::new(&code).lex() {
+                Ok(token_array) => token_array,
+                Err(error) => {
+                    execute!(
+                        stderr(),
+                        SetForegroundColor(Color::Red),
+                        Print("Failed to document : ".bold()),
+                        ResetColor,
+                        Print(format!("{}:{error}\n", path.to_string_lossy())),
+                    )
+                    .ok();
+
+                    return None;
+                }
+            };
+
+            Some(Header {
+                path: relative_path.to_path_buf(),
+                items: parse_items(&code, &token_array),
+            })
+        })
+        .collect();
+
+    headers.retain(|header| !header.items.is_empty());
+    headers.sort_by(|header, other| header.path.cmp(&other.path));
+
+    Package {
+        name,
+        description: None,
+        is_dependency,
+        headers,
+    }
+}
+
+/// Find the function prototypes and the type definitions of a header with the doc comment right
+/// above them, either a `/** */` comment or consecutive `///` lines.
+///
+/// Items are searched at the file level, in namespaces and in `extern "C"` blocks.
+fn parse_items(code: &str, token_array: &TokenArray) -> Vec<Item> {
+    let tokens = &token_array.tokens;
+    let mut items = Vec::new();
+    let mut doc: Vec<String> = Vec::new();
+    let mut newline_count = 0;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        match tokens[index] {
+            Token::Space | Token::Tab => {}
+            Token::Newline => {
+                newline_count += 1;
+
+                // A blank line detaches the comments above
+                if newline_count > 1 {
+                    doc.clear();
+                }
+            }
+            Token::SimpleComment(comment) => {
+                match comment.strip_prefix('/').or(comment.strip_prefix('!')) {
+                    Some(line) if !line.starts_with(['/', '<']) => doc.push(
+                        line.strip_prefix(' ')
+                            .unwrap_or(line)
+                            .trim_end()
+                            .to_string(),
+                    ),
+                    _ => doc.clear(),
+                }
+
+                newline_count = 0;
+            }
+            Token::MultilineComment(comment) => {
+                doc.clear();
+
+                if let Some(text) = comment.strip_prefix('*').or(comment.strip_prefix('!')) {
+                    doc.extend(text.lines().map(|line| {
+                        let line = line.trim();
+                        let line = line.strip_prefix('*').unwrap_or(line);
+
+                        line.strip_prefix(' ').unwrap_or(line).to_string()
+                    }));
+                }
+
+                newline_count = 0;
+            }
+            Token::Macro(_) => {
+                while index + 1 < tokens.len()
+                    && !(tokens[index + 1] == Token::Newline
+                        && tokens[index] != Token::Unknown('\\'))
+                {
+                    index += 1;
+                }
+
+                doc.clear();
+                newline_count = 0;
+            }
+            Token::Namespace => {
+                // Items of a namespace are documented like the ones of the file
+                while index < tokens.len()
+                    && !matches!(tokens[index], Token::LeftBrace | Token::SemiColon)
+                {
+                    index += 1;
+                }
+
+                doc.clear();
+                newline_count = 0;
+            }
+            Token::Extern
+                if next_code(tokens, index + 1)
+                    .filter(|index| matches!(tokens[*index], Token::StringValue(_)))
+                    .and_then(|index| next_code(tokens, index + 1))
+                    .is_some_and(|index| tokens[index] == Token::LeftBrace) =>
+            {
+                index = next_code(tokens, next_code(tokens, index + 1).unwrap() + 1).unwrap();
+            }
+            Token::LeftBrace | Token::RightBrace | Token::SemiColon => {
+                doc.clear();
+                newline_count = 0;
+            }
+            Token::EndOfFile => break,
+            _ => {
+                let end = declaration_end(tokens, index);
+                let doc = take(&mut doc).join("\n");
+
+                if let Some(item) = parse_item(code, token_array, index, end, doc.trim()) {
+                    items.push(item);
+                }
+
+                newline_count = 0;
+                index = end;
+            }
+        }
+
+        index += 1;
+    }
+
+    items
+}
+
+fn next_code(tokens: &[Token], start: usize) -> Option<usize> {
+    (start..tokens.len()).find(|index| !is_blank(&tokens[*index]))
+}
+
+fn matching_brace(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::LeftBrace => depth += 1,
+            Token::RightBrace => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tokens.len() - 1
+}
+
+/// Index of the `;` ending the declaration starting at `start`, or of the `}` ending a function
+/// body.
+fn declaration_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut is_function = false;
+    let mut has_equal = false;
+    let mut index = start;
+
+    while index < tokens.len() {
+        match tokens[index] {
+            Token::LeftParenthesis | Token::LeftBracket => {
+                is_function |= depth == 0 && tokens[index] == Token::LeftParenthesis;
+                depth += 1;
+            }
+            Token::RightParenthesis | Token::RightBracket => depth = depth.saturating_sub(1),
+            Token::Equal if depth == 0 => has_equal = true,
+            Token::LeftBrace => {
+                index = matching_brace(tokens, index);
+
+                if depth == 0 && is_function && !has_equal {
+                    return index;
+                }
+            }
+            Token::SemiColon if depth == 0 => return index,
+            // A directive ends a macro call without semicolon
+            Token::Macro(_) | Token::EndOfFile if index > start => return index - 1,
+            _ => {}
+        }
+
+        index += 1;
+    }
+
+    tokens.len() - 1
+}
+
+fn parse_item(
+    code: &str,
+    token_array: &TokenArray,
+    start: usize,
+    end: usize,
+    doc: &str,
+) -> Option<Item> {
+    let TokenArray { tokens, spans } = token_array;
+    let code_indices: Vec<usize> = (start..=end)
+        .filter(|index| !is_blank(&tokens[*index]))
+        .collect();
+    let token = |position: usize| tokens[code_indices[position]];
+    let mut position = 0;
+    let mut is_typedef = false;
+
+    // The template parameters, attributes and typedef come before the kind of item
+    while position < code_indices.len() {
+        match token(position) {
+            Token::Typedef => is_typedef = true,
+            Token::Attribute(_) => {}
+            Token::Template => {
+                let mut depth = 0;
+
+                while position + 1 < code_indices.len() {
+                    position += 1;
+
+                    match token(position) {
+                        Token::LeftAngle => depth += 1,
+                        Token::RightAngle => depth -= 1,
+                        _ => {}
+                    }
+
+                    if depth <= 0 {
+                        break;
+                    }
+                }
+            }
+            _ => break,
+        }
+
+        position += 1;
+    }
+
+    let kind = match code_indices.get(position).map(|index| tokens[*index]) {
+        Some(Token::Struct) => Some(ItemKind::Struct),
+        Some(Token::Class) => Some(ItemKind::Class),
+        Some(Token::Union) => Some(ItemKind::Union),
+        Some(Token::Enum) => Some(ItemKind::Enum),
+        _ => None,
+    };
+    let opening = (position..code_indices.len()).find(|position| {
+        matches!(
+            token(*position),
+            Token::LeftBrace | Token::LeftParenthesis | Token::SemiColon | Token::Equal
+        )
+    })?;
+    let identifiers = |positions: std::ops::Range<usize>| {
+        positions.filter_map(move |position| match token(position) {
+            Token::Identifier(identifier) if identifier != "final" => Some(identifier),
+            _ => None,
+        })
+    };
+    let references = |name: &str| {
+        let mut references: Vec<String> = Vec::new();
+
+        for identifier in identifiers(0..code_indices.len()) {
+            if identifier != name && !references.iter().any(|reference| reference == identifier) {
+                references.push(identifier.to_string());
+            }
+        }
+
+        references
+    };
+
+    if let (Some(kind), Token::LeftBrace) = (kind, token(opening)) {
+        // The name is before the base classes, after the export macros
+        let name_end = (position..opening)
+            .find(|position| token(*position) == Token::Colon)
+            .unwrap_or(opening);
+        let tag = identifiers(position + 1..name_end).next_back();
+        let name = if is_typedef {
+            let closing = code_indices
+                .iter()
+                .rposition(|index| tokens[*index] == Token::RightBrace)?;
+
+            identifiers(closing..code_indices.len()).next_back().or(tag)
+        } else {
+            tag
+        }?;
+
+        return Some(Item {
+            kind,
+            name: name.to_string(),
+            declaration: dedent(code, spans[start].start, spans[end].end),
+            doc: doc.to_string(),
+            references: references(name),
+        });
+    }
+
+    // Forward declarations, macro calls, variables and function pointer types are not items
+    if is_typedef || token(opening) != Token::LeftParenthesis || opening < position + 2 {
+        return None;
+    }
+
+    let Token::Identifier(name) = token(opening - 1) else {
+        return None;
+    };
+    let signature_end =
+        match (opening..code_indices.len()).find(|position| token(*position) == Token::LeftBrace) {
+            Some(body) => code_indices[body - 1],
+            None if token(code_indices.len() - 1) == Token::SemiColon => {
+                code_indices[code_indices.len() - 2]
+            }
+            None => code_indices[code_indices.len() - 1],
+        };
+    let declaration = code[spans[start].start..spans[signature_end].end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        + ";";
+
+    Some(Item {
+        kind: ItemKind::Function,
+        name: name.to_string(),
+        declaration,
+        doc: doc.to_string(),
+        references: references(name),
+    })
+}
+
+/// Code between `start` and `end`, without the indentation of its first line on the next ones.
+fn dedent(code: &str, start: usize, end: usize) -> String {
+    let line_start = code[..start].rfind('\n').map_or(0, |index| index + 1);
+    let indentation = &code[line_start..start];
+    let indentation = if indentation.trim().is_empty() {
+        indentation
+    } else {
+        ""
+    };
+
+    code[start..end]
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            if index == 0 {
+                line
+            } else {
+                line.strip_prefix(indentation).unwrap_or(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Doc comment split into its text and its `@param` and `@return` commands.
+#[derive(Default)]
+struct DocComment {
+    paragraphs: Vec<String>,
+    parameters: Vec<(String, String)>,
+    returns: Option<String>,
+}
+
+fn parse_doc(doc: &str) -> DocComment {
+    #[derive(PartialEq)]
+    enum Section {
+        Text,
+        Parameter,
+        Returns,
+    }
+
+    let mut doc_comment = DocComment::default();
+    let mut section = Section::Text;
+    let mut paragraph = String::new();
+    let append = |text: &mut String, line: &str| {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+
+        text.push_str(line);
+    };
+
+    for line in doc.lines().map(str::trim) {
+        let command = line.strip_prefix(['@', '\\']).map(|command| {
+            command
+                .split_once(char::is_whitespace)
+                .map_or((command, ""), |(command, text)| (command, text.trim()))
+        });
+
+        match command {
+            Some((command, text)) if command.starts_with("param") => {
+                let (name, text) = text
+                    .split_once(char::is_whitespace)
+                    .map_or((text, ""), |(name, text)| (name, text.trim()));
+
+                doc_comment
+                    .parameters
+                    .push((name.to_string(), text.to_string()));
+                section = Section::Parameter;
+            }
+            Some(("return" | "returns", text)) => {
+                doc_comment.returns = Some(text.to_string());
+                section = Section::Returns;
+            }
+            Some(("brief", text)) => {
+                append(&mut paragraph, text);
+                section = Section::Text;
+            }
+            _ if line.is_empty() => {
+                if !paragraph.is_empty() {
+                    doc_comment.paragraphs.push(take(&mut paragraph));
+                }
+
+                section = Section::Text;
+            }
+            _ => match section {
+                Section::Text => append(&mut paragraph, line),
+                Section::Parameter => {
+                    append(&mut doc_comment.parameters.last_mut().unwrap().1, line)
+                }
+                Section::Returns => append(doc_comment.returns.as_mut().unwrap(), line),
+            },
+        }
+    }
+
+    if !paragraph.is_empty() {
+        doc_comment.paragraphs.push(paragraph);
+    }
+
+    doc_comment
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape `code` for HTML and link the documented names it uses, except `name`.
+fn link_code(code: &str, name: &str, links: &HashMap<String, Link>) -> String {
+    let mut html = String::new();
+    let mut rest = code;
+
+    while let Some(char) = rest.chars().next() {
+        let length = rest
+            .find(|char: char| !(char.is_ascii_alphanumeric() || char == '_'))
+            .unwrap_or(rest.len());
+
+        if length == 0 {
+            html += &escape(&char.to_string());
+            rest = &rest[char.len_utf8()..];
+
+            continue;
+        }
+
+        let word = &rest[..length];
+
+        match links.get(word) {
+            Some(link) if word != name => {
+                html += &format!("<a href=\"{}\">{word}</a>", link.href("../", "html"))
+            }
+            _ => html += word,
+        }
+
+        rest = &rest[length..];
+    }
+
+    html
+}
+
+fn html_page(title: &str, root: &str, body: &str) -> String {
+    let title = escape(title);
+
+    formatdoc!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+        <head>
+        <meta charset="utf-8">
+        <title>{title}</title>
+        <style>{STYLE}</style>
+        </head>
+        <body>
+        <nav><a href="{root}index.html">Packages</a></nav>
+        <h1>{title}</h1>
+        {body}</body>
+        </html>
+        "#
+    )
+}
+
+fn package_html(package: &Package, links: &HashMap<String, Link>) -> String {
+    let mut body = String::new();
+
+    if let Some(description) = &package.description {
+        body += &format!("<p>{}</p>\n", escape(description));
+    }
+
+    for header in package.headers.iter() {
+        body += &format!("<h2>{}</h2>\n", escape(&header.path.to_string_lossy()));
+
+        for item in header.items.iter() {
+            let doc_comment = parse_doc(&item.doc);
+
+            body += &format!(
+                "<section id=\"{}\">\n<h3>{} <code>{}</code></h3>\n<pre><code>{}</code></pre>\n",
+                item.anchor(),
+                item.kind,
+                item.name,
+                link_code(&item.declaration, &item.name, links)
+            );
+
+            for paragraph in doc_comment.paragraphs.iter() {
+                body += &format!("<p>{}</p>\n", escape(paragraph));
+            }
+
+            if !doc_comment.parameters.is_empty() {
+                body += "<h4>Parameters</h4>\n<dl>\n";
+
+                for (name, text) in doc_comment.parameters.iter() {
+                    body += &format!(
+                        "<dt><code>{}</code></dt><dd>{}</dd>\n",
+                        escape(name),
+                        escape(text)
+                    );
+                }
+
+                body += "</dl>\n";
+            }
+
+            if let Some(returns) = &doc_comment.returns {
+                body += &format!("<h4>Returns</h4>\n<p>{}</p>\n", escape(returns));
+            }
+
+            body += "</section>\n";
+        }
+    }
+
+    html_page(&package.name, "../", &body)
+}
+
+fn package_markdown(package: &Package, links: &HashMap<String, Link>) -> String {
+    let mut markdown = format!("# {}\n", package.name);
+
+    if let Some(description) = &package.description {
+        markdown += &format!("\n{description}\n");
+    }
+
+    for header in package.headers.iter() {
+        let path = header.path.to_string_lossy();
+        let language = if path.ends_with(".h") { "c" } else { "cpp" };
+
+        markdown += &format!("\n## `{path}`\n");
+
+        for item in header.items.iter() {
+            let doc_comment = parse_doc(&item.doc);
+
+            markdown += &format!(
+                "\n<a id=\"{}\"></a>\n\n### {} `{}`\n\n```{language}\n{}\n```\n",
+                item.anchor(),
+                item.kind,
+                item.name,
+                item.declaration
+            );
+
+            for paragraph in doc_comment.paragraphs.iter() {
+                markdown += &format!("\n{paragraph}\n");
+            }
+
+            if !doc_comment.parameters.is_empty() {
+                markdown += "\n**Parameters**\n\n";
+
+                for (name, text) in doc_comment.parameters.iter() {
+                    markdown += &format!("- `{name}`: {text}\n");
+                }
+            }
+
+            if let Some(returns) = &doc_comment.returns {
+                markdown += &format!("\n**Returns** {returns}\n");
+            }
+
+            let see_also: Vec<String> = item
+                .references
+                .iter()
+                .filter_map(|reference| {
+                    links
+                        .get(reference)
+                        .map(|link| format!("[`{reference}`]({})", link.href("../", "md")))
+                })
+                .collect();
+
+            if !see_also.is_empty() {
+                markdown += &format!("\nSee also: {}\n", see_also.join(", "));
+            }
+        }
+    }
+
+    markdown
+}
+
+fn index_html(packages: &[Package]) -> String {
+    let mut body = String::new();
+
+    for (title, is_dependency) in [("Project", false), ("Dependencies", true)] {
+        let mut list = String::new();
+
+        for package in packages
+            .iter()
+            .filter(|package| package.is_dependency == is_dependency)
+        {
+            let name = escape(&package.name);
+
+            list += &format!("<li><a href=\"{name}/index.html\">{name}</a></li>\n");
+        }
+
+        if !list.is_empty() {
+            body += &format!("<h2>{title}</h2>\n<ul>\n{list}</ul>\n");
+        }
+    }
+
+    html_page("Packages", "", &body)
+}
+
+fn index_markdown(packages: &[Package]) -> String {
+    let mut markdown = "# Packages\n".to_string();
+
+    for (title, is_dependency) in [("Project", false), ("Dependencies", true)] {
+        let list: String = packages
+            .iter()
+            .filter(|package| package.is_dependency == is_dependency)
+            .map(|package| format!("- [{0}]({0}/index.md)\n", package.name))
+            .collect();
+
+        if !list.is_empty() {
+            markdown += &format!("\n## {title}\n\n{list}");
+        }
+    }
+
+    markdown
+}
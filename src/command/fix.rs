@@ -0,0 +1,312 @@
+use std::{
+    fs::{read_dir, read_to_string, write},
+    io::{stderr, stdout, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crossterm::{
+    execute,
+    style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
+};
+use similar::TextDiff;
+
+use crate::{
+    config::{lint::GuardStyle, ProjectConfig},
+    file::is_header_file,
+};
+
+use super::{
+    find_include_guard, find_pragma_once,
+    format::lexer::{is_blank, Token, Tokenizer},
+    get_project_path, FormatError,
+};
+
+/// Add the missing include guards of the project headers and rename the mismatched ones.
+///
+/// With `check`, nothing is written and a diff is printed instead. Returns `true` when at least
+/// one header changed or would change.
+pub fn fix_guards(config_file: String, check: bool) -> anyhow::Result<bool> {
+    let (project_path, project_config_path) = &get_project_path(&config_file);
+    let project_config = ProjectConfig::load(project_config_path)?;
+    let Some(package) = &project_config.package else {
+        return Ok(false);
+    };
+    let package_name = package.get_name(project_path);
+    let roots: Vec<PathBuf> = package
+        .includes
+        .iter()
+        .chain(package.sources.iter())
+        .map(|path| project_path.join(path))
+        .collect();
+    let mut headers = Vec::new();
+
+    for root in roots.iter() {
+        find_headers(root, &mut headers);
+    }
+
+    headers.sort();
+    headers.dedup();
+
+    let mut is_changed = false;
+
+    for header in headers {
+        // The deepest root is the one the header is included from
+        let relative_path = roots
+            .iter()
+            .filter_map(|root| header.strip_prefix(root).ok())
+            .min_by_key(|relative_path| relative_path.components().count())
+            .unwrap_or(&header);
+        let guard = guard_name(&package_name, relative_path);
+        let code = read_to_string(&header)?;
+        let fixed_code = match fix_guard(&code, &guard, project_config.lint.guard_style) {
+            Ok(fixed_code) => fixed_code,
+            Err(error) => {
+                execute!(
+                    stderr(),
+                    SetForegroundColor(Color::Red),
+                    Print("Failed to fix : ".bold()),
+                    ResetColor,
+                    Print(format!("{}:{error}\n", header.to_string_lossy())),
+                )?;
+
+                continue;
+            }
+        };
+
+        if fixed_code == code {
+            continue;
+        }
+
+        is_changed = true;
+
+        if check {
+            let path = header.to_string_lossy();
+            let diff = TextDiff::from_lines(&code, &fixed_code)
+                .unified_diff()
+                .header(&path, &path)
+                .to_string();
+
+            write!(stdout(), "{diff}")?;
+        } else {
+            write(&header, fixed_code)?;
+        }
+    }
+
+    Ok(is_changed)
+}
+
+fn find_headers(dir_path: &Path, headers: &mut Vec<PathBuf>) {
+    if let Ok(read_dir) = read_dir(dir_path) {
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+
+            if path.is_dir() {
+                find_headers(&path, headers);
+            } else if path.extension().is_some_and(is_header_file) {
+                headers.push(path);
+            }
+        }
+    }
+}
+
+/// Guard macro of a header, like `MYLIB_NET_SOCKET_H` for `net/socket.h` in the package `mylib`.
+fn guard_name(package_name: &str, relative_path: &Path) -> String {
+    let name = package_name.to_string() + "_" + &relative_path.to_string_lossy();
+
+    name.chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() {
+                char.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Add or rename the include guard of `code` to `guard`, or replace it with `#pragma once`.
+fn fix_guard(code: &str, guard: &str, guard_style: GuardStyle) -> Result<String, FormatError> {
+    let token_array = Tokenizer::new(code).lex()?;
+    let tokens = &token_array.tokens;
+    let spans = &token_array.spans;
+    // Code starts after the leading comments, like a license
+    let code_start = tokens
+        .iter()
+        .position(|token| !is_blank(token))
+        .map(|index| {
+            let start = spans[index].start;
+
+            code[..start].rfind('\n').map_or(0, |index| index + 1)
+        });
+    let opening = match guard_style {
+        GuardStyle::Ifndef => format!("#ifndef {guard}\n#define {guard}"),
+        GuardStyle::PragmaOnce => "#pragma once".to_string(),
+    };
+    let closing = format!("#endif // {guard}\n");
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut has_closing = true;
+
+    match (
+        guard_style,
+        find_pragma_once(tokens),
+        find_include_guard(tokens),
+    ) {
+        (GuardStyle::Ifndef, _, Some(include_guard)) => {
+            let Token::Identifier(name) = tokens[include_guard.ifndef_name] else {
+                unreachable!()
+            };
+
+            if name != guard {
+                edits.push((
+                    spans[include_guard.ifndef_name].start..spans[include_guard.ifndef_name].end,
+                    guard.to_string(),
+                ));
+                edits.push((
+                    spans[include_guard.define_name].start..spans[include_guard.define_name].end,
+                    guard.to_string(),
+                ));
+
+                // A comment naming the guard after `#endif`
+                let comment = (include_guard.endif + 1..tokens.len())
+                    .find(|index| !matches!(tokens[*index], Token::Space | Token::Tab))
+                    .filter(|index| {
+                        matches!(
+                            tokens[*index],
+                            Token::SimpleComment(comment) | Token::MultilineComment(comment)
+                                if comment.contains(name)
+                        )
+                    });
+
+                if let Some(comment) = comment {
+                    let span = spans[comment];
+
+                    edits.push((
+                        span.start..span.end,
+                        code[span.start..span.end].replace(name, guard),
+                    ));
+                }
+            }
+        }
+        (GuardStyle::PragmaOnce, Some(_), _) => {}
+        (GuardStyle::Ifndef, Some(pragma), None) => {
+            let once = (pragma + 1..tokens.len())
+                .find(|index| !is_blank(&tokens[*index]))
+                .unwrap();
+
+            edits.push((spans[pragma].start..spans[once].end, opening));
+            has_closing = false;
+        }
+        (GuardStyle::PragmaOnce, None, Some(include_guard)) => {
+            let endif_end = (include_guard.endif..tokens.len())
+                .find(|index| tokens[*index] == Token::Newline)
+                .map_or(code.len(), |index| spans[index].start);
+
+            edits.push((
+                spans[include_guard.ifndef].start..spans[include_guard.define_name].end,
+                opening,
+            ));
+            edits.push((spans[include_guard.endif].start..endif_end, String::new()));
+        }
+        (_, None, None) => {
+            match code_start {
+                Some(code_start) => edits.push((code_start..code_start, opening + "\n\n")),
+                None => {
+                    let end = code.trim_end().len();
+                    let separator = if end == 0 { "" } else { "\n\n" };
+
+                    edits.push((end..code.len(), separator.to_string() + &opening + "\n"));
+                }
+            }
+
+            has_closing = false;
+        }
+    }
+
+    let mut fixed_code = code.to_string();
+
+    edits.sort_by_key(|(range, _)| range.start);
+
+    for (range, replacement) in edits.into_iter().rev() {
+        fixed_code.replace_range(range, &replacement);
+    }
+
+    if guard_style == GuardStyle::PragmaOnce {
+        if fixed_code != code {
+            fixed_code = fixed_code.trim_end().to_string() + "\n";
+        }
+    } else if !has_closing {
+        fixed_code = fixed_code.trim_end().to_string() + "\n\n" + &closing;
+    }
+
+    Ok(fixed_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(code: &str) -> String {
+        fix_guard(code, "PKG_CFG_H", GuardStyle::Ifndef).unwrap()
+    }
+
+    #[test]
+    fn adds_missing_guard() {
+        assert_eq!(
+            fix("int f(void);\n"),
+            "#ifndef PKG_CFG_H\n#define PKG_CFG_H\n\nint f(void);\n\n#endif // PKG_CFG_H\n"
+        );
+    }
+
+    #[test]
+    fn keeps_matching_guard() {
+        let code = "#ifndef PKG_CFG_H\n#define PKG_CFG_H\n\nint f(void);\n\n#endif // PKG_CFG_H\n";
+
+        assert_eq!(fix(code), code);
+    }
+
+    #[test]
+    fn renames_mismatched_guard() {
+        assert_eq!(
+            fix("#ifndef OLD_H\n#define OLD_H\n\n#ifdef A\nint f(void);\n#endif\n\n#endif // OLD_H\n"),
+            "#ifndef PKG_CFG_H\n#define PKG_CFG_H\n\n#ifdef A\nint f(void);\n#endif\n\n#endif // PKG_CFG_H\n"
+        );
+    }
+
+    #[test]
+    fn keeps_defined_constant() {
+        assert_eq!(
+            fix("#ifndef BUFSIZE\n#define BUFSIZE 64\n#endif\n"),
+            "#ifndef PKG_CFG_H\n#define PKG_CFG_H\n\n#ifndef BUFSIZE\n#define BUFSIZE 64\n#endif\n\n#endif // PKG_CFG_H\n"
+        );
+    }
+
+    #[test]
+    fn keeps_conditional_followed_by_code() {
+        assert_eq!(
+            fix("#ifndef A\n#define A\nint f(void);\n#endif\nint g(void);\n"),
+            "#ifndef PKG_CFG_H\n#define PKG_CFG_H\n\n#ifndef A\n#define A\nint f(void);\n#endif\nint g(void);\n\n#endif // PKG_CFG_H\n"
+        );
+    }
+
+    #[test]
+    fn keeps_conditional_closed_before_the_end() {
+        assert_eq!(
+            fix("#ifndef A\n#define A\n#endif\n#ifdef B\nint f(void);\n#endif\n"),
+            "#ifndef PKG_CFG_H\n#define PKG_CFG_H\n\n#ifndef A\n#define A\n#endif\n#ifdef B\nint f(void);\n#endif\n\n#endif // PKG_CFG_H\n"
+        );
+    }
+
+    #[test]
+    fn replaces_guard_with_pragma_once() {
+        assert_eq!(
+            fix_guard(
+                "#ifndef OLD_H\n#define OLD_H\n\nint f(void);\n\n#endif // OLD_H\n",
+                "PKG_CFG_H",
+                GuardStyle::PragmaOnce
+            )
+            .unwrap(),
+            "#pragma once\n\nint f(void);\n"
+        );
+    }
+}
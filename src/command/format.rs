@@ -1,158 +1,321 @@
 use std::{
-    io::stderr,
-    path::{Path, PathBuf},
+    error::Error,
+    fmt,
+    fs::{read_dir, read_to_string, write},
+    io::{stderr, stdin, stdout, Read, Write},
+    path::{self, Path, PathBuf},
+    sync::Arc,
 };
 
-use async_recursion::async_recursion;
 use crossterm::{
     execute,
     style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
 };
 use glob::glob;
 use hashbrown::HashSet;
-use lexer::{Token, Tokenizer};
-use tokio::{
-    fs::{read_dir, read_to_string, write},
-    task::{yield_now, JoinSet},
-};
+use lexer::{is_blank, Span, Token, TokenArray, Tokenizer};
+use rayon::prelude::*;
+use similar::TextDiff;
+
+use changed::{format_lines, get_changed_lines, ChangedLines};
+use includes::sort_includes;
+use wrap::{width, wrap};
 
 use crate::{
-    config::ProjectConfig,
+    config::{
+        format::{
+            ArgumentsLayout, BraceStyle, DirectiveIndent, FormatConfig, MacroBodies,
+            PointerAlignment,
+        },
+        package::PackageConfig,
+        ProjectConfig,
+    },
     file::{get_includes, get_language, Language},
 };
 
 use super::get_project_path;
 
-mod lexer;
+mod changed;
+mod includes;
+pub mod lexer;
+mod wrap;
 
 #[derive(Debug, Clone)]
 pub struct FormatOptions {
     pub tab: String,
+    /// Width of a tabulation, used to measure the lines indented with tabs
+    pub tab_size: usize,
+    pub max_width: usize,
+    pub arguments: ArgumentsLayout,
+    pub macro_bodies: MacroBodies,
+    pub indent_directives: DirectiveIndent,
+    pub sort_includes: bool,
+    pub braces: BraceStyle,
+    pub pointer_alignment: PointerAlignment,
+    pub max_empty_lines: usize,
+    pub align_assignments: bool,
+    pub align_declarations: bool,
+    pub align_macros: bool,
+    pub align_comments: bool,
+    /// Project and include directories, used to classify the includes when sorting them
+    pub project_path: PathBuf,
+    pub include_paths: Vec<PathBuf>,
+    /// Lines changed in git, only the statements overlapping them are formatted
+    pub changed_lines: Option<Arc<ChangedLines>>,
 }
 
-pub async fn format(
-    files: Vec<String>,
-    config_file: String,
-    format_options: &FormatOptions,
-) -> anyhow::Result<()> {
-    let mut join_set = JoinSet::new();
+impl From<&FormatConfig> for FormatOptions {
+    fn from(format_config: &FormatConfig) -> Self {
+        let tab_size = format_config.tab_size.unwrap_or(4);
 
-    if files.is_empty() {
-        let (project_path, project_config_path) = &get_project_path(&config_file);
+        Self {
+            tab: if format_config.use_tabs.unwrap_or(false) {
+                "\t".to_string()
+            } else {
+                " ".repeat(tab_size)
+            },
+            tab_size,
+            max_width: format_config.max_width.unwrap_or(100),
+            arguments: format_config.arguments.unwrap_or_default(),
+            macro_bodies: format_config.macro_bodies.unwrap_or_default(),
+            indent_directives: format_config.indent_directives.unwrap_or_default(),
+            sort_includes: format_config.sort_includes.unwrap_or(false),
+            braces: format_config.braces.unwrap_or_default(),
+            pointer_alignment: format_config.pointer_alignment.unwrap_or_default(),
+            max_empty_lines: format_config.max_empty_lines.unwrap_or(1),
+            align_assignments: format_config.align_assignments.unwrap_or(false),
+            align_declarations: format_config.align_declarations.unwrap_or(false),
+            align_macros: format_config.align_macros.unwrap_or(false),
+            align_comments: format_config.align_comments.unwrap_or(false),
+            project_path: PathBuf::new(),
+            include_paths: Vec::new(),
+            changed_lines: None,
+        }
+    }
+}
 
+#[derive(Debug, Clone, Copy)]
+pub struct FormatFlags {
+    pub check: bool,
+    pub diff: bool,
+}
+
+/// Returns `true` when at least one file is not properly formatted.
+///
+/// With a `changed` git revision, only the lines changed since it are formatted.
+pub fn format(
+    files: Vec<String>,
+    config_file: String,
+    mut format_config: FormatConfig,
+    changed: Option<String>,
+    flags: &FormatFlags,
+) -> anyhow::Result<bool> {
+    let mut paths = Vec::new();
+    let (project_path, project_config_path) = &get_project_path(&config_file);
+    let changed_lines = changed
+        .map(|base| get_changed_lines(project_path, &base))
+        .transpose()?
+        .map(Arc::new);
+
+    let format_options = if files.is_empty() {
         match ProjectConfig::load(project_config_path) {
             Ok(project_config) => {
+                merge_project_format(&mut format_config, project_config.format, project_path);
+
+                let mut format_options = FormatOptions::from(&format_config);
+
+                format_options.project_path = project_path.clone();
+                format_options.changed_lines = changed_lines.clone();
+
+                if let Some(package) = &project_config.package {
+                    format_options.include_paths = include_paths(package);
+                }
+
                 if let Some(package) = project_config.package {
                     let mut explored_path = HashSet::new();
 
                     for path in package.sources.iter() {
-                        format_dir(
-                            &mut join_set,
+                        find_dir_files(
+                            &mut paths,
                             &mut explored_path,
                             &project_path.join(path),
-                            &project_path,
+                            project_path,
                             &package.includes,
-                            format_options,
-                        )
-                        .await;
+                        );
                     }
                 }
+
+                format_options
             }
-            Err(error) => ProjectConfig::handle_error(error, project_config_path)?,
+            Err(error) => return Err(error.into()),
         }
     } else {
+        let mut format_options =
+            load_format_options(project_path, project_config_path, format_config);
+
+        format_options.changed_lines = changed_lines;
+
         for file in files {
             for path in glob(&file)? {
-                join_set.spawn({
-                    let format_options = format_options.clone();
-
-                    async move { apply_format(&path?, &format_options).await }
-                });
+                paths.push(path?);
             }
         }
+
+        format_options
+    };
+
+    let results: Vec<anyhow::Result<bool>> = paths
+        .par_iter()
+        .map(|path| apply_format(path, &format_options, flags))
+        .collect();
+    let mut is_unformatted = false;
+
+    for result in results {
+        is_unformatted |= result?;
     }
 
-    while let Some(handle) = join_set.join_next().await {
-        handle??;
+    Ok(is_unformatted)
+}
+
+/// Format the code read from stdin as if it was the file `assume_filename`, and write it to stdout.
+///
+/// The config is the one of the project of `assume_filename` when there is one. Returns `true`
+/// when the code couldn't be formatted.
+pub fn format_stdin(
+    assume_filename: Option<String>,
+    config_file: String,
+    format_config: FormatConfig,
+) -> anyhow::Result<bool> {
+    let path = PathBuf::from(assume_filename.unwrap_or("<stdin>".to_string()));
+    let config_file = path::absolute(&path)?
+        .ancestors()
+        .skip(1)
+        .map(|path| path.join("Maky.toml"))
+        .find(|path| path.is_file())
+        .map_or(config_file, |path| path.to_string_lossy().to_string());
+    let (project_path, project_config_path) = &get_project_path(&config_file);
+    let format_options = load_format_options(project_path, project_config_path, format_config);
+    let mut code = String::new();
+
+    stdin().read_to_string(&mut code)?;
+
+    match format_code(&code, &path, &format_options) {
+        Ok(formatted_code) => {
+            write!(stdout(), "{formatted_code}")?;
+
+            Ok(false)
+        }
+        Err(error) => {
+            execute!(
+                stderr(),
+                SetForegroundColor(Color::Red),
+                Print("Failed to format : ".bold()),
+                ResetColor,
+                Print(format!("{}:{error}\n", path.to_string_lossy())),
+            )?;
+
+            Ok(true)
+        }
     }
+}
 
-    Ok(())
+/// Options to format files outside of the project sources, with the config of the project if any.
+fn load_format_options(
+    project_path: &Path,
+    project_config_path: &Path,
+    mut format_config: FormatConfig,
+) -> FormatOptions {
+    let project_config = ProjectConfig::load_without_processing(project_config_path).ok();
+
+    let project_format_config = project_config
+        .as_ref()
+        .map(|project_config| project_config.format.clone())
+        .unwrap_or_default();
+
+    merge_project_format(&mut format_config, project_format_config, project_path);
+
+    let mut format_options = FormatOptions::from(&format_config);
+
+    format_options.project_path = project_path.to_path_buf();
+
+    if let Some(package) = project_config.and_then(|project_config| project_config.package) {
+        format_options.include_paths = include_paths(&package);
+    }
+
+    format_options
+}
+
+/// Fill the unset options with the project ones, or with its `.clang-format` when it has none.
+fn merge_project_format(
+    format_config: &mut FormatConfig,
+    project_format_config: FormatConfig,
+    project_path: &Path,
+) {
+    if project_format_config.is_empty() {
+        if let Some(clang_format_config) = FormatConfig::load_clang_format(project_path) {
+            format_config.merge(clang_format_config);
+        }
+    } else {
+        format_config.merge(project_format_config);
+    }
+}
+
+/// Directories searched for the includes, including the headers of the dependencies.
+fn include_paths(package: &PackageConfig) -> Vec<PathBuf> {
+    let mut include_paths = package.includes.clone();
+
+    include_paths.push(Path::new(".maky/include").to_path_buf());
+    include_paths
 }
 
-#[async_recursion]
-async fn format_dir(
-    join_set: &mut JoinSet<anyhow::Result<()>>,
+/// Collect the C and C++ files of `dir_path` and the files they include.
+pub fn find_dir_files(
+    paths: &mut Vec<PathBuf>,
     explored_path: &mut HashSet<PathBuf>,
     dir_path: &Path,
     project_path: &Path,
-    include_path_vec: &Vec<PathBuf>,
-    format_options: &FormatOptions,
+    include_path_vec: &[PathBuf],
 ) {
-    if let Ok(mut read_dir) = read_dir(dir_path).await {
-        while let Ok(Some(entry)) = read_dir.next_entry().await {
+    if let Ok(read_dir) = read_dir(dir_path) {
+        for entry in read_dir.flatten() {
             let path = entry.path();
 
             if !explored_path.contains(&path) {
                 explored_path.insert(path.clone());
 
                 if path.is_file() {
-                    format_file(
-                        join_set,
-                        explored_path,
-                        &path,
-                        project_path,
-                        include_path_vec,
-                        format_options,
-                    )
-                    .await;
+                    find_files(paths, explored_path, &path, project_path, include_path_vec);
                 } else if path.is_dir() {
-                    format_dir(
-                        join_set,
-                        explored_path,
-                        &path,
-                        project_path,
-                        include_path_vec,
-                        format_options,
-                    )
-                    .await;
+                    find_dir_files(paths, explored_path, &path, project_path, include_path_vec);
                 }
             }
         }
     }
 }
 
-#[async_recursion]
-async fn format_file(
-    join_set: &mut JoinSet<anyhow::Result<()>>,
+/// Collect `file_path` if it is a C or C++ file, and the files it includes.
+fn find_files(
+    paths: &mut Vec<PathBuf>,
     explored_path: &mut HashSet<PathBuf>,
     file_path: &Path,
     project_path: &Path,
-    include_path_vec: &Vec<PathBuf>,
-    format_options: &FormatOptions,
+    include_path_vec: &[PathBuf],
 ) {
     if let Some(extension) = file_path.extension() {
         if let Language::C | Language::Cpp = get_language(extension) {
-            join_set.spawn({
-                let file_path = file_path.to_path_buf();
-                let format_options = format_options.clone();
-
-                async move { apply_format(&file_path, &format_options).await }
-            });
+            paths.push(file_path.to_path_buf());
 
-            if let Ok(code) = read_to_string(&file_path).await {
-                for include_path in get_includes(&file_path, project_path, include_path_vec, &code)
-                {
+            if let Ok(code) = read_to_string(file_path) {
+                for include_path in get_includes(file_path, project_path, include_path_vec, &code) {
                     if !explored_path.contains(&include_path) {
                         explored_path.insert(include_path.clone());
-                        format_file(
-                            join_set,
+                        find_files(
+                            paths,
                             explored_path,
                             &include_path,
                             project_path,
                             include_path_vec,
-                            format_options,
-                        )
-                        .await;
+                        );
                     }
                 }
             }
@@ -160,57 +323,281 @@ async fn format_file(
     }
 }
 
-async fn apply_format(path: &Path, format_options: &FormatOptions) -> anyhow::Result<()> {
-    let code = read_to_string(path).await?;
-    let tokenizer = Tokenizer::new(&code);
-    let formatter = Formatter::new(tokenizer.lex().await, format_options.clone());
-
-    let Ok(code) = formatter.format().await else {
-        execute!(
-            stderr(),
-            SetForegroundColor(Color::Red),
-            Print("Failed to format : ".bold()),
-            ResetColor,
-            Print(path.to_string_lossy()),
-            Print("\n"),
-        )?;
-
-        return Ok(());
+/// Returns `true` when the file is not properly formatted.
+fn apply_format(
+    path: &Path,
+    format_options: &FormatOptions,
+    flags: &FormatFlags,
+) -> anyhow::Result<bool> {
+    let lines = match &format_options.changed_lines {
+        Some(changed_lines) => match path
+            .canonicalize()
+            .ok()
+            .and_then(|path| changed_lines.get(&path))
+        {
+            Some(lines) => Some(lines),
+            None => return Ok(false),
+        },
+        None => None,
+    };
+    let code = read_to_string(path)?;
+    let formatted_code = match format_code(&code, path, format_options) {
+        Ok(formatted_code) => match lines {
+            Some(lines) => format_lines(&code, &formatted_code, lines),
+            None => formatted_code,
+        },
+        Err(error) => {
+            execute!(
+                stderr(),
+                SetForegroundColor(Color::Red),
+                Print("Failed to format : ".bold()),
+                ResetColor,
+                Print(format!("{}:{error}\n", path.to_string_lossy())),
+            )?;
+
+            return Ok(true);
+        }
+    };
+
+    if formatted_code == code {
+        return Ok(false);
+    }
+
+    if flags.check || flags.diff {
+        let path = path.to_string_lossy();
+        let diff = TextDiff::from_lines(&code, &formatted_code)
+            .unified_diff()
+            .header(&path, &path)
+            .to_string();
+
+        write!(stdout(), "{diff}")?;
+    } else {
+        write(path, formatted_code)?;
+    }
+
+    Ok(true)
+}
+
+/// Format `code`, making sure that only whitespaces changed and that formatting again gives the same code.
+pub fn format_code(
+    code: &str,
+    path: &Path,
+    format_options: &FormatOptions,
+) -> Result<String, FormatError> {
+    let lex = |code| {
+        let tokens = Tokenizer::new(code).lex()?;
+
+        // Sorted includes couldn't be spliced back into the unchanged lines
+        Ok(
+            if format_options.sort_includes && format_options.changed_lines.is_none() {
+                sort_includes(tokens, path, format_options)
+            } else {
+                tokens
+            },
+        )
     };
+    let tokens = lex(code)?;
+    let formatted_code = Formatter::new(&tokens, format_options).format()?;
+    let formatted_tokens = lex(&formatted_code)?;
+
+    check_tokens(&tokens, &formatted_tokens)?;
+
+    let reformatted_code = Formatter::new(&formatted_tokens, format_options).format()?;
+
+    if reformatted_code != formatted_code {
+        let lines: Vec<&str> = formatted_code.lines().collect();
+        let reformatted_lines: Vec<&str> = reformatted_code.lines().collect();
+        let line_index = (0..)
+            .find(|index| lines.get(*index) != reformatted_lines.get(*index))
+            .unwrap();
+        let column = lines
+            .get(line_index)
+            .unwrap_or(&"")
+            .chars()
+            .zip(reformatted_lines.get(line_index).unwrap_or(&"").chars())
+            .take_while(|(char, reformatted_char)| char == reformatted_char)
+            .count();
+
+        return Err(FormatError {
+            line: line_index + 1,
+            column: column + 1,
+            message:
+                "formatting is not idempotent, the formatted code changes when formatted again"
+                    .to_string(),
+        });
+    }
 
-    write(path, code).await?;
+    Ok(formatted_code)
+}
+
+/// Compare the token streams, ignoring whitespaces and newlines.
+fn check_tokens(tokens: &TokenArray, formatted_tokens: &TokenArray) -> Result<(), FormatError> {
+    let is_code =
+        |(_, token): &(&Span, &Token)| !matches!(token, Token::Space | Token::Tab | Token::Newline);
+    let mut formatted_tokens = formatted_tokens
+        .spans
+        .iter()
+        .zip(formatted_tokens.tokens.iter())
+        .filter(is_code);
+
+    for (span, token) in tokens
+        .spans
+        .iter()
+        .zip(tokens.tokens.iter())
+        .filter(is_code)
+    {
+        let Span { line, column, .. } = *span;
+
+        match formatted_tokens.next() {
+            Some((_, formatted_token)) if is_same_token(token, formatted_token) => {}
+            Some((_, formatted_token)) => {
+                let message = format!("expected `{token}` but found `{formatted_token}`");
+
+                return Err(FormatError {
+                    line,
+                    column,
+                    message: format!("formatting changes the code, {message}"),
+                });
+            }
+            None => {
+                return Err(FormatError {
+                    line,
+                    column,
+                    message: format!("formatting removes the code starting at `{token}`"),
+                });
+            }
+        }
+    }
+
+    if let Some((_, formatted_token)) = formatted_tokens.next() {
+        let Span { line, column, .. } = tokens.spans.last().copied().unwrap_or_default();
+
+        return Err(FormatError {
+            line,
+            column,
+            message: format!("formatting adds `{formatted_token}` to the code"),
+        });
+    }
 
     Ok(())
 }
 
+/// Directives and multiline comments are reindented, so the whitespaces at the start of their lines are ignored.
+fn is_same_token(token: &Token, formatted_token: &Token) -> bool {
+    match (token, formatted_token) {
+        (Token::Macro(name), Token::Macro(formatted_name)) => {
+            name.trim_start() == formatted_name.trim_start()
+        }
+        // Split on every newline, as the `*/` of a reindented comment ends a line of whitespaces
+        (Token::MultilineComment(comment), Token::MultilineComment(formatted_comment)) => comment
+            .split('\n')
+            .map(str::trim_start)
+            .eq(formatted_comment.split('\n').map(str::trim_start)),
+        _ => token == formatted_token,
+    }
+}
+
+#[derive(Debug)]
+pub struct FormatError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for FormatError {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Scope {
     Block,
     Parenthesis,
+    /// Brace initializer, kept on the line of the expression
+    Initializer,
+    /// Namespace body, which is not indented
+    Namespace,
+    /// Enum body, with one enumerator per line
+    Enum,
+    /// Struct, class or union body, whose member declarations can be aligned
+    Record,
 }
 
+/// Kinds of tokens aligned over consecutive lines, in the order they are aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlignmentKind {
+    Declaration,
+    Assignment,
+    Macro,
+    Comment,
+}
+
+/// A token to align with the tokens of the same kind on the neighbouring lines.
+#[derive(Debug, Clone, Copy)]
+struct Alignment {
+    kind: AlignmentKind,
+    /// Offset in the formatted code where the padding goes
+    offset: usize,
+    /// Offset of the aligned token, after the `*` and `&` before a declaration name
+    token_offset: usize,
+}
+
+/// An `#if`, `#ifdef` or `#ifndef` block being formatted.
 #[derive(Debug)]
-struct Formatter {
-    options: FormatOptions,
-    tokens: Vec<Token>,
+struct Conditional {
+    /// Scope when entering the block, each branch starts from it
+    scope: Vec<Scope>,
+    /// Scope at the end of the first branch, which is kept after `#endif`
+    branch_scope: Option<Vec<Scope>>,
+    is_include_guard: bool,
+}
+
+#[derive(Debug)]
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    tokens: &'a [Token<'a>],
+    spans: &'a [Span],
     token_index: usize,
     scope: Vec<Scope>,
+    conditionals: Vec<Conditional>,
     formatted_code: String,
+    /// Length of the formatted code that can't be trimmed, after a directive or a line comment
+    locked_length: usize,
+    /// Index of the first token of the current statement
+    statement_start: usize,
+    /// Whether a trailing return type was found since the start of the statement
+    trailing_return: bool,
+    alignments: Vec<Alignment>,
 }
 
-impl Formatter {
-    pub fn new(tokens: Vec<Token>, options: FormatOptions) -> Self {
+impl<'a> Formatter<'a> {
+    pub fn new(token_array: &'a TokenArray<'a>, options: &'a FormatOptions) -> Self {
         Self {
             options,
-            tokens,
+            tokens: &token_array.tokens,
+            spans: &token_array.spans,
             token_index: 0,
             scope: Vec::new(),
+            conditionals: Vec::new(),
             formatted_code: String::new(),
+            locked_length: 0,
+            statement_start: 0,
+            trailing_return: false,
+            alignments: Vec::new(),
         }
     }
 
     fn tab(&self) -> String {
-        self.options.tab.repeat(self.scope.len())
+        let depth = self
+            .scope
+            .iter()
+            .filter(|scope| **scope != Scope::Namespace)
+            .count();
+
+        self.options.tab.repeat(depth)
     }
 
     fn add_code<T: AsRef<str>>(&mut self, code: T) {
@@ -218,43 +605,811 @@ impl Formatter {
     }
 
     fn newline(&mut self) {
-        self.formatted_code =
-            self.formatted_code.trim_end_matches(' ').to_string() + "\n" + &self.tab()
+        self.formatted_code = self
+            .formatted_code
+            .trim_end_matches([' ', '\t'])
+            .to_string()
+            + "\n"
+            + &self.tab()
     }
 
     fn trim_end(&mut self) {
-        self.formatted_code = self.formatted_code.trim_end().to_string()
+        let length = self.formatted_code.trim_end().len();
+
+        if length < self.locked_length {
+            self.formatted_code.truncate(self.locked_length);
+            self.add_code(self.tab());
+        } else {
+            self.formatted_code.truncate(length);
+        }
+    }
+
+    /// Trim the end of the code and start a new line, unless it's already on a locked line start.
+    fn start_line(&mut self) {
+        self.trim_end();
+
+        if self.formatted_code.trim_end_matches([' ', '\t']).len() != self.locked_length {
+            self.newline();
+        }
+    }
+
+    /// Trim the end of the code and continue the line, unless it's already on a locked line start.
+    fn attach(&mut self) {
+        self.trim_end();
+
+        let length = self.formatted_code.trim_end_matches([' ', '\t']).len();
+
+        if length != self.locked_length && length != 0 {
+            self.add_code(" ");
+        }
+    }
+
+    /// Prevent the code already formatted from being trimmed, up to the last newline.
+    fn lock_line(&mut self) {
+        self.locked_length = self.formatted_code.rfind('\n').map_or(0, |index| index + 1);
     }
 
     fn trim_end_whitespace(&mut self) {
-        self.formatted_code = self.formatted_code.trim_end_matches(' ').to_string()
+        self.formatted_code = self
+            .formatted_code
+            .trim_end_matches([' ', '\t'])
+            .to_string()
+    }
+
+    fn get(&self) -> Result<Token<'a>, FormatError> {
+        self.tokens
+            .get(self.token_index)
+            .copied()
+            .ok_or_else(|| self.error("unexpected end of file"))
     }
 
-    fn get(&self) -> Result<Token, ()> {
-        self.tokens.get(self.token_index).cloned().ok_or(())
+    fn error<T: AsRef<str>>(&self, message: T) -> FormatError {
+        let Span { line, column, .. } = self
+            .spans
+            .get(self.token_index)
+            .or(self.spans.last())
+            .copied()
+            .unwrap_or_default();
+
+        FormatError {
+            line,
+            column,
+            message: message.as_ref().to_string(),
+        }
     }
 
     fn scope(&self) -> Option<Scope> {
         self.scope.last().copied()
     }
 
-    pub async fn format(mut self) -> Result<String, ()> {
-        let mut newline_count = 0u8;
+    /// First token before `index` that is neither a whitespace nor a comment.
+    fn previous_token(&self, index: usize) -> Option<&Token<'a>> {
+        self.tokens[..index]
+            .iter()
+            .rev()
+            .find(|token| !is_blank(token))
+    }
+
+    /// First token after `index` that is neither a whitespace nor a comment.
+    fn following_token(&self, index: usize) -> Option<&Token<'a>> {
+        self.tokens[index + 1..]
+            .iter()
+            .find(|token| !is_blank(token))
+    }
+
+    /// Tokens of the current statement before the current token, without whitespaces and comments.
+    fn statement(&self) -> impl Iterator<Item = &Token<'a>> {
+        self.tokens[self.statement_start.min(self.token_index)..self.token_index]
+            .iter()
+            .filter(|token| !is_blank(token))
+    }
+
+    fn start_statement(&mut self) {
+        self.statement_start = self.token_index + 1;
+        self.trailing_return = false;
+    }
+
+    /// Whether the current newline ends a line of a single identifier or invocation, like
+    /// `__BEGIN_DECLS` or `FOO(a)`, followed by a blank line, a directive or a comment.
+    fn is_bare_invocation_end(&self) -> bool {
+        let statement: Vec<&Token> = self.statement().collect();
+        let is_bare_invocation = matches!(
+            statement[..],
+            [Token::Identifier(_)]
+                | [
+                    Token::Identifier(_),
+                    Token::LeftParenthesis,
+                    ..,
+                    Token::RightParenthesis
+                ]
+        ) && matches!(
+            self.scope(),
+            None | Some(Scope::Block | Scope::Namespace | Scope::Record)
+        ) && !matches!(
+            self.tokens[..self.token_index]
+                .iter()
+                .rfind(|token| !matches!(token, Token::Space | Token::Tab)),
+            Some(Token::SimpleComment(_) | Token::MultilineComment(_))
+        );
+
+        let mut following = self.tokens[self.token_index..]
+            .iter()
+            .filter(|token| !matches!(token, Token::Space | Token::Tab));
+        let newline_count = following
+            .clone()
+            .take_while(|token| **token == Token::Newline)
+            .count();
+
+        is_bare_invocation
+            && (newline_count > 1
+                || matches!(
+                    following.find(|token| **token != Token::Newline),
+                    Some(Token::Macro(_) | Token::SimpleComment(_) | Token::MultilineComment(_))
+                ))
+    }
+
+    /// Whether the current colon ends a label, like `public:`, `case 1:` or `end:`.
+    fn is_label(&self) -> bool {
+        let mut statement = self.statement();
+
+        matches!(
+            (statement.next(), statement.next()),
+            (
+                Some(
+                    Token::Public
+                        | Token::Protected
+                        | Token::Private
+                        | Token::Case
+                        | Token::Default
+                ),
+                _,
+            ) | (Some(Token::Identifier(_)), None)
+        )
+    }
+
+    /// Whether the current arrow introduces the return type of a lambda or an `auto` function.
+    fn is_trailing_return(&self) -> bool {
+        let mut index = self.token_index;
+
+        // Skip the qualifiers between the parameters and the arrow
+        loop {
+            index = match self.tokens[..index]
+                .iter()
+                .rposition(|token| !is_blank(token))
+            {
+                Some(index) => index,
+                None => return false,
+            };
+
+            match &self.tokens[index] {
+                Token::Const | Token::Noexcept | Token::Mutable => {}
+                Token::RightParenthesis => break,
+                _ => return false,
+            }
+        }
+
+        let mut depth = 0usize;
+
+        for index in (0..=index).rev() {
+            match self.tokens[index] {
+                Token::RightParenthesis => depth += 1,
+                Token::LeftParenthesis => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return match self.previous_token(index) {
+                            Some(Token::RightBracket) => true,
+                            Some(Token::Identifier(_)) => {
+                                let name_index = self.tokens[..index]
+                                    .iter()
+                                    .rposition(|token| !is_blank(token))
+                                    .unwrap();
+
+                                self.previous_token(name_index) == Some(&Token::Auto)
+                            }
+                            _ => false,
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    /// Whether the current token is inside template arguments.
+    fn in_template_arguments(&self) -> bool {
+        self.statement().fold(0isize, |depth, token| match token {
+            Token::LeftAngle => depth + 1,
+            Token::RightAngle => depth - 1,
+            _ => depth,
+        }) > 0
+    }
+
+    /// Whether the current `>` ends the parameters of a template declaration.
+    fn is_template_declaration(&self) -> bool {
+        let mut depth = 0usize;
+        let mut opening = None;
+
+        for (index, token) in self.tokens[..self.token_index].iter().enumerate().rev() {
+            match token {
+                Token::RightAngle => depth += 1,
+                Token::LeftAngle if depth == 0 => {
+                    opening = Some(index);
+                    break;
+                }
+                Token::LeftAngle => depth -= 1,
+                _ => {}
+            }
+        }
+
+        let Some(opening) = opening else {
+            return false;
+        };
+
+        // Template parameters of a template parameter are kept on the line
+        let is_nested = self.tokens[self.statement_start.min(opening)..opening]
+            .iter()
+            .fold(0isize, |depth, token| match token {
+                Token::LeftAngle => depth + 1,
+                Token::RightAngle => depth - 1,
+                _ => depth,
+            })
+            > 0;
+
+        !is_nested && self.previous_token(opening) == Some(&Token::Template)
+    }
+
+    /// Scope opened by the current brace.
+    fn brace_scope(&self) -> Scope {
+        if self.statement().any(|token| *token == Token::Namespace) {
+            return Scope::Namespace;
+        }
+
+        match self.previous_token(self.token_index) {
+            Some(Token::Equal | Token::Comma | Token::LeftParenthesis | Token::Return) => {
+                Scope::Initializer
+            }
+            Some(Token::LeftBrace) if self.scope() == Some(Scope::Initializer) => {
+                Scope::Initializer
+            }
+            Some(Token::Identifier(identifier))
+                if *identifier == "override" || *identifier == "final" =>
+            {
+                Scope::Block
+            }
+            _ if self.statement().any(|token| *token == Token::Enum) => Scope::Enum,
+            Some(Token::Identifier(_) | Token::RightAngle) => {
+                let is_declaration = self.statement().any(|token| {
+                    matches!(
+                        token,
+                        Token::Class | Token::Struct | Token::Union | Token::Extern
+                    )
+                });
+
+                if self
+                    .statement()
+                    .any(|token| matches!(token, Token::Class | Token::Struct | Token::Union))
+                {
+                    Scope::Record
+                } else if is_declaration || self.trailing_return {
+                    Scope::Block
+                } else {
+                    Scope::Initializer
+                }
+            }
+            Some(Token::Class | Token::Struct | Token::Union) => Scope::Record,
+            _ => Scope::Block,
+        }
+    }
+
+    /// Whether the `*` or `&` at `index` is part of a declaration type, like in `const Foo *a`.
+    fn is_pointer_declarator(&self, index: usize) -> bool {
+        let Some(mut previous) = self.tokens[..index]
+            .iter()
+            .rposition(|token| !is_blank(token))
+        else {
+            return false;
+        };
+
+        match self.tokens[previous] {
+            Token::Void
+            | Token::Char
+            | Token::Short
+            | Token::Int
+            | Token::Long
+            | Token::Float
+            | Token::Double
+            | Token::Signed
+            | Token::Unsigned
+            | Token::Auto
+            | Token::Const
+            | Token::Volatile
+            | Token::RightAngle => true,
+            Token::Ampersand | Token::Times => self.is_pointer_declarator(previous),
+            // A type name, which can only start a declaration outside of braces and parentheses
+            Token::Identifier(_) => {
+                let is_expression =
+                    matches!(self.scope(), Some(Scope::Initializer | Scope::Parenthesis));
+
+                loop {
+                    let before = self.tokens[..previous]
+                        .iter()
+                        .rposition(|token| !is_blank(token));
+
+                    match before.map(|before| (before, self.tokens[before])) {
+                        Some((before, Token::ScopeResolution | Token::Identifier(_))) => {
+                            previous = before
+                        }
+                        None
+                        | Some((_, Token::SemiColon | Token::LeftBrace | Token::RightBrace)) => {
+                            return !is_expression
+                        }
+                        Some((
+                            _,
+                            Token::Const
+                            | Token::Volatile
+                            | Token::Static
+                            | Token::Extern
+                            | Token::Register
+                            | Token::Inline
+                            | Token::Constexpr
+                            | Token::Mutable
+                            | Token::Virtual
+                            | Token::Friend
+                            | Token::Explicit
+                            | Token::Typedef
+                            | Token::Typename
+                            | Token::Struct
+                            | Token::Union
+                            | Token::Enum
+                            | Token::Class,
+                        )) => return true,
+                        _ => return false,
+                    }
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the current `=` is the first one of an assignment, a variable or an enumerator.
+    fn is_aligned_assignment(&self) -> bool {
+        self.options.align_assignments
+            && matches!(
+                self.scope(),
+                None | Some(Scope::Block | Scope::Namespace | Scope::Enum | Scope::Record)
+            )
+            && !self.in_template_arguments()
+            && self.previous_token(self.token_index) != Some(&Token::Operator)
+    }
+
+    /// Whether the current token is the name of a member variable declared in a struct or a class.
+    fn is_aligned_declaration(&self) -> bool {
+        self.options.align_declarations
+            && self.scope() == Some(Scope::Record)
+            && matches!(self.tokens[self.token_index], Token::Identifier(_))
+            && matches!(
+                self.following_token(self.token_index),
+                Some(
+                    Token::SemiColon
+                        | Token::Equal
+                        | Token::LeftBracket
+                        | Token::Colon
+                        | Token::Comma
+                        | Token::LeftBrace
+                )
+            )
+            && self.statement().next().is_some()
+            && !self.statement().any(|token| {
+                matches!(
+                    token,
+                    Token::LeftParenthesis
+                        | Token::Equal
+                        | Token::Comma
+                        | Token::Using
+                        | Token::Friend
+                        | Token::Operator
+                )
+            })
+    }
+
+    /// Mark the token about to be added to be aligned, with the `*` and `&` just before it.
+    fn align_here(&mut self, kind: AlignmentKind) {
+        let token_offset = self.formatted_code.len();
+        let offset = self.formatted_code.trim_end_matches(['*', '&']).len();
+
+        self.alignments.push(Alignment {
+            kind,
+            offset,
+            token_offset,
+        });
+    }
+
+    /// Pad the aligned tokens to the same column, over the runs of consecutive lines with the same
+    /// indentation.
+    ///
+    /// Blank lines and lines without such a token, like comments, break the runs.
+    fn align(&mut self) {
+        let mut line_starts = vec![0];
+
+        line_starts.extend(
+            self.formatted_code
+                .match_indices('\n')
+                .map(|(index, _)| index + 1),
+        );
+
+        let mut lines: Vec<String> = self.formatted_code.split('\n').map(String::from).collect();
+        // Line index, with the offsets relative to the line start
+        let mut alignments: Vec<(usize, Alignment)> = self
+            .alignments
+            .iter()
+            .map(|alignment| {
+                let line = line_starts.partition_point(|start| *start <= alignment.offset) - 1;
+
+                (
+                    line,
+                    Alignment {
+                        offset: alignment.offset - line_starts[line],
+                        token_offset: alignment.token_offset - line_starts[line],
+                        ..*alignment
+                    },
+                )
+            })
+            .collect();
+        let indentation = |line: &str| line.len() - line.trim_start().len();
+
+        for kind in [
+            AlignmentKind::Declaration,
+            AlignmentKind::Assignment,
+            AlignmentKind::Macro,
+            AlignmentKind::Comment,
+        ] {
+            let mut runs: Vec<Vec<usize>> = Vec::new();
+
+            for (index, (line, alignment)) in alignments.iter().enumerate() {
+                if alignment.kind != kind {
+                    continue;
+                }
+
+                match runs.last_mut() {
+                    // Only the first token of a kind is aligned on each line
+                    Some(run) if alignments[*run.last().unwrap()].0 == *line => {}
+                    Some(run)
+                        if alignments[*run.last().unwrap()].0 + 1 == *line
+                            && (kind == AlignmentKind::Comment
+                                || indentation(&lines[*line])
+                                    == indentation(&lines[*line - 1])) =>
+                    {
+                        run.push(index)
+                    }
+                    _ => runs.push(vec![index]),
+                }
+            }
+
+            for run in runs.into_iter().filter(|run| run.len() > 1) {
+                let columns: Vec<usize> = run
+                    .iter()
+                    .map(|index| {
+                        let (line, alignment) = alignments[*index];
+
+                        width(&lines[line][..alignment.token_offset], self.options)
+                    })
+                    .collect();
+                let max_column = columns.iter().copied().max().unwrap_or(0);
+
+                for (index, column) in run.into_iter().zip(columns) {
+                    let (line, alignment) = alignments[index];
+                    let padding = max_column - column;
+
+                    lines[line].insert_str(alignment.offset, &" ".repeat(padding));
+
+                    // Move the other tokens of the line after the padding
+                    for (other_index, (other_line, other_alignment)) in
+                        alignments.iter_mut().enumerate()
+                    {
+                        if other_index != index
+                            && *other_line == line
+                            && other_alignment.offset >= alignment.offset
+                        {
+                            other_alignment.offset += padding;
+                            other_alignment.token_offset += padding;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.formatted_code = lines.join("\n");
+    }
+
+    /// Index of the first token of the value of the `#define` whose name is at or after `index`.
+    fn macro_value_start(&self, index: usize) -> Option<usize> {
+        let name = (index..self.tokens.len())
+            .find(|index| !matches!(self.tokens[*index], Token::Space | Token::Tab))?;
+
+        if matches!(
+            self.tokens[name],
+            Token::Newline | Token::SimpleComment(_) | Token::Unknown('\\')
+        ) {
+            return None;
+        }
+
+        // The parameters of a function-like macro are part of its name
+        if self.tokens.get(name + 1) == Some(&Token::LeftParenthesis) {
+            return (name + 1..self.tokens.len())
+                .find(|index| self.tokens[*index] == Token::RightParenthesis)
+                .map(|index| index + 1);
+        }
+
+        Some(name + 1)
+    }
+
+    /// Format an access specifier or a `case` label, outdented from the code it introduces.
+    fn label(&mut self, token_string: String) {
+        self.trim_end_whitespace();
+
+        let scope = self.scope.pop();
+
+        self.add_code(self.tab());
+        self.add_code(token_string);
+        self.add_code(" ");
+
+        if let Some(scope) = scope {
+            self.scope.push(scope);
+        }
+
+        self.token_index += 1;
+    }
+
+    /// Index of the first token after `index` that is not a whitespace.
+    fn next_index(&self, index: usize) -> Option<usize> {
+        (index + 1..self.tokens.len())
+            .find(|index| !matches!(self.tokens[*index], Token::Space | Token::Tab))
+    }
+
+    /// Name of the directive starting at `index`.
+    fn directive_name(&self, index: usize) -> String {
+        match &self.tokens[index] {
+            Token::Macro(name) => name.trim_start().to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Whether the directive at `index` opens an include guard wrapping the whole file.
+    fn is_include_guard(&self, index: usize) -> bool {
+        let is_code = |token: &&Token| {
+            !matches!(
+                token,
+                Token::Space
+                    | Token::Tab
+                    | Token::Newline
+                    | Token::SimpleComment(_)
+                    | Token::MultilineComment(_)
+            )
+        };
+
+        if self.tokens[..index].iter().any(|token| is_code(&token)) {
+            return false;
+        }
+
+        let directives: Vec<usize> = (0..self.tokens.len())
+            .filter(|index| matches!(self.tokens[*index], Token::Macro(_)))
+            .collect();
+
+        if self.directive_name(index) != "ifndef" || directives.len() < 3 {
+            return false;
+        }
+
+        let guard = self.next_index(index).map(|index| &self.tokens[index]);
+        let defined = self
+            .next_index(directives[1])
+            .map(|index| &self.tokens[index]);
+
+        if self.directive_name(directives[1]) != "define" || guard.is_none() || guard != defined {
+            return false;
+        }
+
+        // The `#endif` matching the guard must be the last directive, followed only by comments
+        let mut depth = 0;
+
+        for (position, directive) in directives.iter().enumerate() {
+            match self.directive_name(*directive).as_str() {
+                "if" | "ifdef" | "ifndef" => depth += 1,
+                "endif" => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return position == directives.len() - 1
+                            && !self.tokens[*directive..]
+                                .iter()
+                                .skip_while(|token| !matches!(token, Token::Newline))
+                                .any(|token| is_code(&token));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    /// Number of conditional blocks the directives are nested in, not counting the include guard.
+    fn directive_depth(&self) -> usize {
+        self.conditionals
+            .iter()
+            .filter(|conditional| !conditional.is_include_guard)
+            .count()
+    }
+
+    /// Format a preprocessor directive, including its continuation lines.
+    fn format_directive(&mut self) -> Result<(), FormatError> {
+        let name = self.directive_name(self.token_index);
+        let depth = match name.as_str() {
+            "elif" | "else" | "endif" => self.directive_depth().saturating_sub(
+                self.conditionals
+                    .last()
+                    .is_some_and(|conditional| !conditional.is_include_guard)
+                    as usize,
+            ),
+            _ => self.directive_depth(),
+        };
+
+        match name.as_str() {
+            "if" | "ifdef" | "ifndef" => {
+                self.conditionals.push(Conditional {
+                    scope: self.scope.clone(),
+                    branch_scope: None,
+                    is_include_guard: self.is_include_guard(self.token_index),
+                });
+            }
+            "elif" | "else" => {
+                if let Some(conditional) = self.conditionals.last_mut() {
+                    if conditional.branch_scope.is_none() {
+                        conditional.branch_scope = Some(self.scope.clone());
+                    }
+
+                    self.scope = conditional.scope.clone();
+                }
+            }
+            "endif" => {
+                if let Some(Conditional {
+                    branch_scope: Some(branch_scope),
+                    ..
+                }) = self.conditionals.pop()
+                {
+                    self.scope = branch_scope;
+                }
+            }
+            _ => {}
+        }
+
+        let directive = match self.options.indent_directives {
+            DirectiveIndent::None => self.tokens[self.token_index].to_string(),
+            DirectiveIndent::BeforeHash => self.options.tab.repeat(depth) + "#" + &name,
+            DirectiveIndent::AfterHash => "#".to_string() + &self.options.tab.repeat(depth) + &name,
+        };
+        let mut lines = vec![directive.clone()];
+
+        self.token_index += 1;
+
+        let start = self.token_index;
 
         while self.token_index < self.tokens.len() {
-            yield_now().await;
+            let token = self.get()?;
 
+            match token {
+                Token::SimpleComment(_) | Token::Newline => break,
+                Token::Unknown('\\')
+                    if self
+                        .next_index(self.token_index)
+                        .map(|index| &self.tokens[index])
+                        == Some(&Token::Newline) =>
+                {
+                    lines.last_mut().unwrap().push('\\');
+                    lines.push(String::new());
+                    self.token_index = self.next_index(self.token_index).unwrap();
+                }
+                _ => *lines.last_mut().unwrap() += &token.to_string(),
+            }
+
+            self.token_index += 1;
+        }
+
+        if self.options.macro_bodies == MacroBodies::Align && lines.len() > 1 {
+            let last_index = lines.len() - 1;
+
+            for line in lines[..last_index].iter_mut() {
+                line.pop();
+                *line = line.trim_end().to_string();
+            }
+
+            let width = lines[..last_index]
+                .iter()
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0);
+
+            for line in lines[..last_index].iter_mut() {
+                *line += &" ".repeat(width - line.chars().count() + 1);
+                line.push('\\');
+            }
+        }
+
+        self.trim_end_whitespace();
+
+        if !self.formatted_code.is_empty() && !self.formatted_code.ends_with('\n') {
+            self.add_code("\n");
+        }
+
+        // Single line macros are written `#define NAME value`, with the value aligned
+        if self.options.align_macros && name == "define" && lines.len() == 1 {
+            if let Some(value_start) = self.macro_value_start(start) {
+                let render =
+                    |tokens: &[Token]| tokens.iter().map(Token::to_string).collect::<String>();
+                let head = directive + " " + render(&self.tokens[start..value_start]).trim();
+                let value = render(&self.tokens[value_start..self.token_index]);
+                let value = value.trim();
+
+                if !value.is_empty() {
+                    lines[0] = head.clone() + " " + value;
+
+                    let offset = self.formatted_code.len() + head.len() + 1;
+
+                    self.alignments.push(Alignment {
+                        kind: AlignmentKind::Macro,
+                        offset,
+                        token_offset: offset,
+                    });
+                }
+            }
+        }
+
+        self.add_code(lines.join("\n").trim_end());
+
+        // A trailing comment stays on the directive line and locks it itself
+        if matches!(
+            self.tokens.get(self.token_index),
+            Some(Token::SimpleComment(_))
+        ) {
+            self.add_code(" ");
+        } else {
+            self.newline();
+            self.lock_line();
+        }
+
+        Ok(())
+    }
+
+    pub fn format(mut self) -> Result<String, FormatError> {
+        let mut newline_count = 0;
+
+        self.statement_start = 0;
+
+        while self.token_index < self.tokens.len() {
             let token = self.get()?;
             let token_string = token.to_string();
 
-            if token != Token::Newline {
+            // Lines of whitespaces count as blank lines
+            if !matches!(token, Token::Newline | Token::Space | Token::Tab) {
                 newline_count = 0;
             }
 
-            match token.clone() {
+            match token {
+                // A macro invocation without `;`, like `__BEGIN_DECLS`, ends its statement when a
+                // directive or comment follows, which keeps them and the blank lines on their own
+                Token::Newline if newline_count == 0 && self.is_bare_invocation_end() => {
+                    self.newline();
+                    self.start_statement();
+                    newline_count += 1;
+
+                    self.token_index += 1;
+                    continue;
+                }
                 Token::Newline => {
-                    if newline_count < 2 {
-                        if newline_count == 1 {
+                    if newline_count <= self.options.max_empty_lines {
+                        if newline_count > 0 {
                             self.newline();
                         }
 
@@ -268,22 +1423,35 @@ impl Formatter {
                     self.token_index += 1;
                     continue;
                 }
-                Token::Public
-                | Token::Protected
-                | Token::Private
-                | Token::Case
-                | Token::Default => {
-                    self.trim_end_whitespace();
-
-                    let scope = self.scope.pop();
-
-                    self.add_code(self.tab());
+                Token::Public | Token::Protected | Token::Private | Token::Default
+                    if self.following_token(self.token_index) == Some(&Token::Colon) =>
+                {
+                    self.label(token_string);
+                }
+                Token::Case => self.label(token_string),
+                Token::Identifier(_)
+                    if self.statement().next().is_none()
+                        && self.following_token(self.token_index) == Some(&Token::Colon) =>
+                {
+                    self.label(token_string);
+                }
+                Token::Arrow if self.is_trailing_return() => {
+                    self.trim_end();
+                    self.add_code(" ");
                     self.add_code(token_string);
                     self.add_code(" ");
+                    self.trailing_return = true;
 
-                    if let Some(scope) = scope {
-                        self.scope.push(scope);
-                    }
+                    self.token_index += 1;
+                }
+                // The global scope, like in `x ? ::a : ::b`, keeps the space before it
+                Token::ScopeResolution
+                    if !matches!(
+                        self.previous_token(self.token_index),
+                        Some(Token::Identifier(_) | Token::RightAngle | Token::RightParenthesis)
+                    ) =>
+                {
+                    self.add_code(token_string);
 
                     self.token_index += 1;
                 }
@@ -296,14 +1464,28 @@ impl Formatter {
                 Token::Comma => {
                     self.trim_end();
                     self.add_code(token_string);
-                    self.add_code(" ");
+
+                    if self.scope() == Some(Scope::Enum) {
+                        self.newline();
+                    } else {
+                        self.add_code(" ");
+                    }
 
                     self.token_index += 1;
                 }
-                Token::Colon => {
+                Token::Colon if self.is_label() => {
                     self.trim_end();
                     self.add_code(token_string);
                     self.newline();
+                    self.start_statement();
+
+                    self.token_index += 1;
+                }
+                Token::Colon => {
+                    self.trim_end();
+                    self.add_code(" ");
+                    self.add_code(token_string);
+                    self.add_code(" ");
 
                     self.token_index += 1;
                 }
@@ -313,6 +1495,7 @@ impl Formatter {
 
                     if self.scope() != Some(Scope::Parenthesis) {
                         self.newline();
+                        self.start_statement();
                     } else {
                         self.add_code(" ");
                     }
@@ -320,9 +1503,26 @@ impl Formatter {
                     self.token_index += 1;
                 }
                 Token::LeftParenthesis => {
+                    // Function types in template arguments, like `std::function<void(int)>`
+                    if self.in_template_arguments()
+                        && !matches!(
+                            self.previous_token(self.token_index),
+                            Some(Token::LeftAngle | Token::Comma)
+                        )
+                    {
+                        self.trim_end();
+                    }
+
                     for token in self.tokens[..self.token_index].iter().rev() {
                         match token {
-                            Token::Identifier(_) | Token::Sizeof => {
+                            Token::Identifier(_)
+                            | Token::Sizeof
+                            | Token::Alignof
+                            | Token::Decltype
+                            | Token::Noexcept
+                            | Token::StaticAssert
+                            | Token::RightAngle
+                            | Token::RightBracket => {
                                 self.trim_end();
                                 break;
                             }
@@ -339,7 +1539,7 @@ impl Formatter {
                 Token::RightParenthesis => {
                     match self.scope.pop() {
                         Some(Scope::Parenthesis) => {}
-                        _ => return Err(()),
+                        _ => return Err(self.error("unbalanced `)`")),
                     }
 
                     self.trim_end();
@@ -349,31 +1549,109 @@ impl Formatter {
                     self.token_index += 1;
                 }
                 Token::LeftBrace => {
-                    self.trim_end();
-                    self.newline();
-                    self.add_code(token_string);
-                    self.scope.push(Scope::Block);
-                    self.newline();
+                    let scope = self.brace_scope();
+
+                    if scope == Scope::Initializer {
+                        if matches!(
+                            self.previous_token(self.token_index),
+                            Some(Token::Identifier(_) | Token::RightAngle)
+                        ) {
+                            self.trim_end();
+                        }
+
+                        self.add_code(token_string);
+                        self.scope.push(scope);
+                    } else {
+                        match self.options.braces {
+                            BraceStyle::Allman => self.start_line(),
+                            BraceStyle::Attach => self.attach(),
+                        }
+
+                        self.add_code(token_string);
+                        self.scope.push(scope);
+                        self.newline();
+                        self.start_statement();
+                    }
 
                     self.token_index += 1;
                 }
                 Token::RightBrace => {
                     match self.scope.pop() {
-                        Some(Scope::Block) => {}
-                        _ => return Err(()),
+                        Some(Scope::Initializer) => {
+                            self.trim_end();
+                            self.add_code(token_string);
+                            self.add_code(" ");
+                        }
+                        Some(Scope::Block | Scope::Namespace | Scope::Enum | Scope::Record) => {
+                            self.start_line();
+                            self.add_code(token_string);
+
+                            if self.options.braces == BraceStyle::Attach
+                                && self.following_token(self.token_index) == Some(&Token::Else)
+                            {
+                                self.add_code(" ");
+                            } else {
+                                self.newline();
+                            }
+
+                            self.start_statement();
+                        }
+                        _ => return Err(self.error("unbalanced `}`")),
+                    }
+
+                    self.token_index += 1;
+                }
+                Token::LeftBracket => {
+                    // A bracket starting an expression is a lambda capture list
+                    let is_lambda = matches!(
+                        self.previous_token(self.token_index),
+                        None | Some(
+                            Token::Equal
+                                | Token::LeftParenthesis
+                                | Token::Comma
+                                | Token::Return
+                                | Token::LeftBrace
+                                | Token::RightBrace
+                                | Token::SemiColon
+                                | Token::QuestionMark
+                                | Token::Colon
+                                | Token::And
+                                | Token::Or
+                        )
+                    );
+
+                    if !is_lambda {
+                        self.trim_end();
                     }
 
-                    self.trim_end();
-                    self.newline();
                     self.add_code(token_string);
-                    self.newline();
 
                     self.token_index += 1;
                 }
-                Token::LeftBracket => {
+                Token::LeftAngle => {
+                    if self.previous_token(self.token_index) != Some(&Token::Template) {
+                        self.trim_end();
+                    }
+
+                    self.add_code(token_string);
+
+                    self.token_index += 1;
+                }
+                Token::RightAngle => {
                     self.trim_end();
                     self.add_code(token_string);
 
+                    if self.is_template_declaration() {
+                        self.newline();
+                    } else {
+                        self.add_code(" ");
+                    }
+
+                    self.token_index += 1;
+                }
+                Token::EncodingPrefix(_) => {
+                    self.add_code(token_string);
+
                     self.token_index += 1;
                 }
                 Token::RightBracket => {
@@ -389,13 +1667,36 @@ impl Formatter {
 
                     self.token_index += 1;
                 }
+                Token::Ampersand | Token::Times if self.is_pointer_declarator(self.token_index) => {
+                    match self.options.pointer_alignment {
+                        PointerAlignment::Left => {
+                            self.trim_end();
+                            self.add_code(token_string);
+                            self.add_code(" ");
+                        }
+                        PointerAlignment::Right => self.add_code(token_string),
+                        PointerAlignment::Middle => {
+                            self.add_code(token_string);
+
+                            if !matches!(
+                                self.following_token(self.token_index),
+                                Some(Token::Ampersand | Token::Times)
+                            ) {
+                                self.add_code(" ");
+                            }
+                        }
+                    }
+
+                    self.token_index += 1;
+                }
                 Token::Ampersand | Token::Times | Token::Plus | Token::Minus => {
                     let token_index = self.token_index;
 
                     self.add_code(token_string);
 
-                    loop {
-                        self.token_index -= 1;
+                    // Look behind for an operand, making the operator binary, down to the first token
+                    while let Some(previous_index) = self.token_index.checked_sub(1) {
+                        self.token_index = previous_index;
 
                         let token = self.get()?;
 
@@ -424,9 +1725,10 @@ impl Formatter {
                     self.token_index = token_index + 1;
                 }
                 Token::Decrement | Token::Increment => {
-                    self.token_index -= 1;
-
-                    let token = self.get()?;
+                    let token = match self.token_index.checked_sub(1) {
+                        Some(previous_index) => self.tokens[previous_index],
+                        None => Token::Space,
+                    };
 
                     if token != Token::Space {
                         self.trim_end();
@@ -438,7 +1740,7 @@ impl Formatter {
                         self.add_code(" ");
                     }
 
-                    self.token_index += 2;
+                    self.token_index += 1;
                 }
                 Token::For | Token::While | Token::If => {
                     self.add_code(token_string);
@@ -446,10 +1748,11 @@ impl Formatter {
 
                     self.token_index += 1;
 
+                    // Keep `constexpr` in `if constexpr (...)`
                     while self.token_index < self.tokens.len() {
                         let token = self.get()?;
 
-                        if token == Token::LeftParenthesis {
+                        if !matches!(token, Token::Space | Token::Tab | Token::Newline) {
                             break;
                         }
 
@@ -457,29 +1760,29 @@ impl Formatter {
                     }
                 }
                 Token::Macro(_) => {
-                    self.trim_end_whitespace();
-                    self.add_code(token_string);
-
-                    self.token_index += 1;
+                    self.format_directive()?;
+                    self.statement_start = self.token_index;
+                }
+                Token::SimpleComment(_) => {
+                    // A comment ending a line of code stays on it
+                    let is_trailing = self.tokens[..self.token_index]
+                        .iter()
+                        .rev()
+                        .find(|token| !matches!(token, Token::Space | Token::Tab))
+                        .is_some_and(|token| *token != Token::Newline);
 
-                    while self.token_index < self.tokens.len() {
-                        let token = self.get()?;
+                    if is_trailing {
+                        self.trim_end();
+                        self.add_code(" ");
 
-                        match token {
-                            Token::SimpleComment(_) | Token::MultilineComment(_) => break,
-                            Token::Newline => {
-                                self.newline();
-                                break;
-                            }
-                            _ => self.add_code(token.to_string()),
+                        if self.options.align_comments {
+                            self.align_here(AlignmentKind::Comment);
                         }
-
-                        self.token_index += 1;
                     }
-                }
-                Token::SimpleComment(_) => {
+
                     self.add_code(token_string);
                     self.newline();
+                    self.lock_line();
 
                     self.token_index += 1;
                 }
@@ -511,6 +1814,12 @@ impl Formatter {
                     }
                 }
                 _ => {
+                    if token == Token::Equal && self.is_aligned_assignment() {
+                        self.align_here(AlignmentKind::Assignment);
+                    } else if self.is_aligned_declaration() {
+                        self.align_here(AlignmentKind::Declaration);
+                    }
+
                     self.add_code(token_string);
                     self.add_code(" ");
 
@@ -519,6 +1828,79 @@ impl Formatter {
             }
         }
 
-        Ok(self.formatted_code.trim().to_string())
+        match self.scope() {
+            Some(
+                Scope::Block | Scope::Initializer | Scope::Namespace | Scope::Enum | Scope::Record,
+            ) => return Err(self.error("unclosed `{`")),
+            Some(Scope::Parenthesis) => return Err(self.error("unclosed `(`")),
+            None => {}
+        }
+
+        self.align();
+
+        let formatted_code = wrap(self.formatted_code.trim().to_string(), self.options);
+
+        // Text files end with a newline, so that formatted files don't differ from the ones editors
+        // save
+        if formatted_code.is_empty() {
+            Ok(formatted_code)
+        } else {
+            Ok(formatted_code + "\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(code: &str) -> String {
+        let format_options = FormatOptions::from(&FormatConfig::default());
+
+        format_code(code, Path::new("test.cpp"), &format_options).unwrap()
+    }
+
+    #[test]
+    fn keeps_space_between_colon_and_scope_resolution() {
+        assert_eq!(
+            format("int f() { return x ? ::a(1) : ::b(2); }\n"),
+            "int f()\n{\n    return x ? ::a(1) : ::b(2);\n}\n"
+        );
+    }
+
+    #[test]
+    fn ends_invocation_line_before_comment() {
+        assert_eq!(
+            format("int a;\n\n__BEGIN_DECLS\n\n/* c */\nint y;\n"),
+            "int a;\n\n__BEGIN_DECLS\n\n/* c */\nint y;\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comment_off_invocation_line() {
+        assert_eq!(format("A\n/* c */\n#undef x\n"), "A\n/* c */\n#undef x\n");
+    }
+
+    #[test]
+    fn keeps_blank_line_after_invocation() {
+        assert_eq!(format("FOO\n\n#undef x\n"), "FOO\n\n#undef x\n");
+    }
+
+    #[test]
+    fn keeps_trailing_comment_of_enumerator() {
+        assert_eq!(
+            format("enum e {\n    A /* c */\n#define A A\n};\n"),
+            "enum e\n{\n    A /* c */\n#define A A\n};\n"
+        );
+    }
+
+    #[test]
+    fn reindents_starred_comment() {
+        assert_eq!(format("/*\n** a\n*/\nint a;\n"), "/*\n ** a\n */\nint a;\n");
+    }
+
+    #[test]
+    fn counts_whitespace_lines_as_blank() {
+        assert_eq!(format("int a;\n\n    \n\nint b;\n"), "int a;\n\nint b;\n");
     }
 }
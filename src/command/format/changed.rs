@@ -0,0 +1,171 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use git2::{DiffOptions, Repository};
+use hashbrown::HashMap;
+
+use super::lexer::{Token, TokenArray, Tokenizer};
+
+pub type ChangedLines = HashMap<PathBuf, Vec<Range<usize>>>;
+
+/// Lines, starting at 1, changed in the working tree since `base`, for each changed file.
+///
+/// Paths are canonicalized, untracked files are changed as a whole.
+pub fn get_changed_lines(project_path: &Path, base: &str) -> anyhow::Result<ChangedLines> {
+    let repository = Repository::discover(project_path)?;
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("the git repository has no working tree"))?
+        .to_path_buf();
+    let tree = repository.revparse_single(base)?.peel_to_tree()?;
+    let mut diff_options = DiffOptions::new();
+
+    diff_options
+        .context_lines(0)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+
+    let diff = repository.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut diff_options))?;
+    let mut changed_lines = ChangedLines::new();
+
+    diff.foreach(
+        &mut |_, _| true,
+        None,
+        Some(&mut |delta, hunk| {
+            let path = delta
+                .new_file()
+                .path()
+                .and_then(|path| workdir.join(path).canonicalize().ok());
+
+            if let Some(path) = path {
+                let start = hunk.new_start() as usize;
+                let lines = match hunk.new_lines() as usize {
+                    // A removal changes the lines around it
+                    0 => start..start + 2,
+                    count => start..start + count,
+                };
+
+                changed_lines.entry(path).or_default().push(lines);
+            }
+
+            true
+        }),
+        None,
+    )?;
+
+    Ok(changed_lines)
+}
+
+/// Splice `formatted_code` into `code`, taking only the statements overlapping `lines` from it.
+///
+/// The rest of `code` is left byte for byte identical. Both codes must have the same tokens
+/// besides the whitespaces, which the formatter already checks.
+pub fn format_lines(code: &str, formatted_code: &str, lines: &[Range<usize>]) -> String {
+    let (Ok(tokens), Ok(formatted_tokens)) = (
+        Tokenizer::new(code).lex(),
+        Tokenizer::new(formatted_code).lex(),
+    ) else {
+        return code.to_string();
+    };
+    let statements = get_statements(&tokens);
+    let formatted_statements = get_statements(&formatted_tokens);
+
+    if statements.len() != formatted_statements.len() {
+        return code.to_string();
+    }
+
+    let mut spliced_code = String::with_capacity(code.len());
+    let mut start = 0;
+    let mut formatted_start = 0;
+
+    for (statement, formatted_statement) in statements.iter().zip(formatted_statements.iter()) {
+        if lines
+            .iter()
+            .any(|range| range.start < statement.lines.end && statement.lines.start < range.end)
+        {
+            spliced_code += &formatted_code[formatted_start..formatted_statement.end];
+        } else {
+            spliced_code += &code[start..statement.end];
+        }
+
+        start = statement.end;
+        formatted_start = formatted_statement.end;
+    }
+
+    spliced_code + &code[start..]
+}
+
+#[derive(Debug)]
+struct Statement {
+    /// Lines of the statement, starting at 1
+    lines: Range<usize>,
+    /// Byte offset just after the last token of the statement
+    end: usize,
+}
+
+/// Split the code into statements, ending after a `;` or a brace outside parentheses, after a line
+/// comment or at the end of a directive.
+fn get_statements(token_array: &TokenArray) -> Vec<Statement> {
+    let TokenArray { tokens, spans } = token_array;
+    let mut statements = Vec::new();
+    let mut statement_line = None;
+    let mut statement_end = 0;
+    let mut end_line = 1;
+    let mut depth = 0usize;
+    let mut is_directive = false;
+
+    for (index, (token, span)) in tokens.iter().zip(spans.iter()).enumerate() {
+        match token {
+            Token::Space | Token::Tab => continue,
+            Token::Newline => {
+                // The newline ending a directive is not part of it
+                if is_directive && tokens[..index].last() != Some(&Token::Unknown('\\')) {
+                    is_directive = false;
+                }
+
+                continue;
+            }
+            Token::Macro(_) => is_directive = true,
+            Token::LeftParenthesis => depth += 1,
+            Token::RightParenthesis => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+
+        let start_line = *statement_line.get_or_insert(span.line);
+        let ends_directive = is_directive
+            && *token != Token::Unknown('\\')
+            && tokens[index + 1..]
+                .iter()
+                .find(|token| !matches!(token, Token::Space | Token::Tab))
+                .is_none_or(|token| *token == Token::Newline);
+        let ends_statement = match token {
+            Token::SemiColon | Token::LeftBrace | Token::RightBrace => depth == 0 && !is_directive,
+            Token::SimpleComment(_) => true,
+            _ => ends_directive,
+        };
+
+        statement_end = span.end;
+        end_line = span.line + token.to_string().matches('\n').count();
+
+        if ends_statement {
+            statements.push(Statement {
+                lines: start_line..end_line + 1,
+                end: statement_end,
+            });
+            statement_line = None;
+        }
+    }
+
+    // The code can end without ending its last statement
+    if let Some(start_line) = statement_line {
+        statements.push(Statement {
+            lines: start_line..end_line + 1,
+            end: statement_end,
+        });
+    }
+
+    statements
+}
@@ -0,0 +1,173 @@
+use std::{ops::Range, path::Path};
+
+use crate::file::resolve_include;
+
+use super::{
+    lexer::{Token, TokenArray},
+    FormatOptions,
+};
+
+/// Groups of includes, in the order they are sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum IncludeGroup {
+    /// Header matching the file, like `"foo.h"` in `foo.c`
+    Main,
+    /// `"..."` headers of the project
+    Project,
+    /// `<deps/...>` headers of the dependencies
+    Dependency,
+    /// `<...>` headers of the system
+    System,
+}
+
+/// Sort each run of consecutive `#include` lines of the file at `path`, grouping them and separating
+/// the groups with a blank line.
+///
+/// Any other line ends the run, so includes never move across code, comments or conditional blocks.
+pub fn sort_includes<'a>(
+    token_array: TokenArray<'a>,
+    path: &Path,
+    options: &FormatOptions,
+) -> TokenArray<'a> {
+    let TokenArray { tokens, spans } = &token_array;
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        if *token == Token::Newline {
+            lines.push(line_start..index);
+            line_start = index + 1;
+        }
+    }
+
+    lines.push(line_start..tokens.len());
+
+    // Blank lines added between the groups are `None`
+    let mut sorted_lines: Vec<Option<Range<usize>>> = Vec::with_capacity(lines.len());
+    let mut index = 0;
+
+    while index < lines.len() {
+        if include_name(&tokens[lines[index].clone()]).is_none() {
+            sorted_lines.push(Some(lines[index].clone()));
+            index += 1;
+            continue;
+        }
+
+        let mut includes = Vec::new();
+
+        while index < lines.len() {
+            let line = &tokens[lines[index].clone()];
+
+            if let Some(name) = include_name(line) {
+                includes.push((
+                    include_group(&name, path, options),
+                    name,
+                    lines[index].clone(),
+                ));
+            } else if is_blank(line) {
+                // Blank lines are part of the run only when more includes follow them
+                let next_line = lines[index..]
+                    .iter()
+                    .map(|line| &tokens[line.clone()])
+                    .find(|line| !is_blank(line));
+
+                if next_line.is_none_or(|line| include_name(line).is_none()) {
+                    break;
+                }
+            } else {
+                break;
+            }
+
+            index += 1;
+        }
+
+        includes.sort_by(|(group, name, _), (other_group, other_name, _)| {
+            group.cmp(other_group).then_with(|| name.cmp(other_name))
+        });
+
+        for (include_index, (group, _, line)) in includes.iter().enumerate() {
+            if include_index > 0 && includes[include_index - 1].0 != *group {
+                sorted_lines.push(None);
+            }
+
+            sorted_lines.push(Some(line.clone()));
+        }
+    }
+
+    let mut sorted_array = TokenArray::default();
+    // Newlines keep the span of the newline ending the previous line, or of the last one
+    let mut newline_span = spans
+        .get(lines[0].end)
+        .or(spans.last())
+        .copied()
+        .unwrap_or_default();
+
+    for (line_index, line) in sorted_lines.into_iter().enumerate() {
+        if line_index > 0 {
+            sorted_array.tokens.push(Token::Newline);
+            sorted_array.spans.push(newline_span);
+        }
+
+        if let Some(line) = line {
+            sorted_array.tokens.extend_from_slice(&tokens[line.clone()]);
+            sorted_array.spans.extend_from_slice(&spans[line.clone()]);
+
+            if let Some(span) = spans.get(line.end) {
+                newline_span = *span;
+            }
+        }
+    }
+
+    sorted_array
+}
+
+/// Name of the included file with its delimiters, like `<stdio.h>`, if `line` is an include.
+fn include_name(line: &[Token]) -> Option<String> {
+    let mut tokens = line
+        .iter()
+        .skip_while(|token| matches!(token, Token::Space | Token::Tab));
+
+    match tokens.next() {
+        Some(Token::Macro(name)) if name.trim_start() == "include" => {}
+        _ => return None,
+    }
+
+    let name = tokens
+        .take_while(|token| !matches!(token, Token::SimpleComment(_) | Token::MultilineComment(_)))
+        .map(Token::to_string)
+        .collect::<String>()
+        .trim()
+        .to_string();
+    let is_quoted = name.len() > 2 && name.starts_with('"') && name.ends_with('"');
+    let is_angled = name.len() > 2 && name.starts_with('<') && name.ends_with('>');
+
+    (is_quoted || is_angled).then_some(name)
+}
+
+fn include_group(name: &str, path: &Path, options: &FormatOptions) -> IncludeGroup {
+    let include = Path::new(&name[1..name.len() - 1]);
+
+    if name.starts_with('"')
+        && include.file_stem().is_some()
+        && include.file_stem() == path.file_stem()
+    {
+        return IncludeGroup::Main;
+    }
+
+    let dependencies_path = options.project_path.join(".maky/include/deps");
+
+    match resolve_include(path, &options.project_path, &options.include_paths, include) {
+        Some(include_path) if include_path.starts_with(&dependencies_path) => {
+            IncludeGroup::Dependency
+        }
+        Some(_) => IncludeGroup::Project,
+        None if include.starts_with("deps") => IncludeGroup::Dependency,
+        None if name.starts_with('"') => IncludeGroup::Project,
+        None => IncludeGroup::System,
+    }
+}
+
+fn is_blank(line: &[Token]) -> bool {
+    line.iter()
+        .all(|token| matches!(token, Token::Space | Token::Tab))
+}
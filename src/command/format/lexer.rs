@@ -1,20 +1,23 @@
-use std::ops::Range;
+use std::fmt;
 
-use tokio::task::yield_now;
+use super::FormatError;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Token {
-    Identifier(String),
-    Macro(String),
-    SimpleComment(String),
-    MultilineComment(String),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    Identifier(&'a str),
+    Macro(&'a str),
+    Attribute(&'a str),
+    SimpleComment(&'a str),
+    MultilineComment(&'a str),
 
     // literals
-    DigitValue(String),
-    FloatValue(String),
-    StringValue(String),
-    BoolValue(String),
-    CharValue(String),
+    DigitValue(&'a str),
+    FloatValue(&'a str),
+    StringValue(&'a str),
+    RawStringValue(&'a str),
+    EncodingPrefix(&'a str),
+    BoolValue(&'a str),
+    CharValue(&'a str),
 
     // operators
     Plus,
@@ -66,6 +69,8 @@ pub enum Token {
     RightBracket,
     LeftBrace,
     RightBrace,
+    LeftAngle,
+    RightAngle,
     Colon,
     SemiColon,
     Comma,
@@ -121,6 +126,16 @@ pub enum Token {
     While,
     Null,
     Namespace,
+    Typename,
+    Using,
+    Constexpr,
+    Decltype,
+    Explicit,
+    Mutable,
+    Noexcept,
+    Nullptr,
+    StaticAssert,
+    Alignof,
 
     // unique
     Unknown(char),
@@ -130,16 +145,19 @@ pub enum Token {
     EndOfFile,
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let string = match self {
             Token::Identifier(identifier) => identifier.to_string(),
             Token::Macro(r#macro) => "#".to_string() + r#macro,
+            Token::Attribute(attribute) => "[[".to_string() + attribute + "]]",
             Token::SimpleComment(comment) => "//".to_string() + comment,
             Token::MultilineComment(comment) => "/*".to_string() + comment + "*/",
             Token::DigitValue(digit) => digit.to_string(),
             Token::FloatValue(float) => float.to_string(),
             Token::StringValue(string) => "\"".to_string() + string + "\"",
+            Token::RawStringValue(string) => string.to_string(),
+            Token::EncodingPrefix(prefix) => prefix.to_string(),
             Token::BoolValue(r#bool) => r#bool.to_string(),
             Token::CharValue(char) => "'".to_string() + char + "'",
             Token::Plus => "+".to_string(),
@@ -185,6 +203,8 @@ impl ToString for Token {
             Token::RightBracket => "]".to_string(),
             Token::LeftBrace => "{".to_string(),
             Token::RightBrace => "}".to_string(),
+            Token::LeftAngle => "<".to_string(),
+            Token::RightAngle => ">".to_string(),
             Token::Colon => ":".to_string(),
             Token::SemiColon => ";".to_string(),
             Token::Comma => ",".to_string(),
@@ -215,7 +235,7 @@ impl ToString for Token {
             Token::Case => "case".to_string(),
             Token::Extern => "extern".to_string(),
             Token::Protected => "protected".to_string(),
-            Token::Throw => "thrown".to_string(),
+            Token::Throw => "throw".to_string(),
             Token::Catch => "catch".to_string(),
             Token::Float => "float".to_string(),
             Token::Public => "public".to_string(),
@@ -243,463 +263,745 @@ impl ToString for Token {
             Token::Long => "long".to_string(),
             Token::Struct => "struct".to_string(),
             Token::Namespace => "namespace".to_string(),
-        }
+            Token::Typename => "typename".to_string(),
+            Token::Using => "using".to_string(),
+            Token::Constexpr => "constexpr".to_string(),
+            Token::Decltype => "decltype".to_string(),
+            Token::Explicit => "explicit".to_string(),
+            Token::Mutable => "mutable".to_string(),
+            Token::Noexcept => "noexcept".to_string(),
+            Token::Nullptr => "nullptr".to_string(),
+            Token::StaticAssert => "static_assert".to_string(),
+            Token::Alignof => "alignof".to_string(),
+        };
+
+        f.write_str(&string)
     }
 }
 
-pub type TokenArray = Vec<Token>;
+/// Position of a token in the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Byte offset of the start of the token
+    pub start: usize,
+    /// Byte offset just after the end of the token
+    pub end: usize,
+    /// Line of the start of the token, starting at 1
+    pub line: usize,
+    /// Column of the start of the token in chars, starting at 1
+    pub column: usize,
+}
 
-pub struct Tokenizer {
-    code: Vec<(usize, char)>,
+/// Tokens of a code, along with their spans.
+#[derive(Debug, Clone, Default)]
+pub struct TokenArray<'a> {
+    pub tokens: Vec<Token<'a>>,
+    pub spans: Vec<Span>,
+}
+
+pub struct Tokenizer<'a> {
+    code: &'a str,
     index: usize,
+    line: usize,
+    column: usize,
 }
 
-impl Tokenizer {
-    pub fn new(code: &str) -> Self {
+impl<'a> Tokenizer<'a> {
+    pub fn new(code: &'a str) -> Self {
         Self {
-            code: (code.to_string() + " ")
-                .replace("\r\n", "\n")
-                .replace("\r", "\n")
-                .chars()
-                .enumerate()
-                .collect(),
+            code,
             index: 0,
+            line: 1,
+            column: 1,
         }
     }
 
-    fn next(&mut self) -> Option<(usize, char)> {
-        let char = self.code.get(self.index).copied();
-
-        self.index += 1;
-        char
+    fn peek(&self) -> Option<char> {
+        self.code[self.index..].chars().next()
     }
 
-    fn skip(&mut self) {
-        self.index += 1;
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+
+        self.index += char.len_utf8();
+
+        // A `\r\n` is a single newline, counted on its `\n`
+        if char == '\n' || (char == '\r' && self.peek() != Some('\n')) {
+            self.line += 1;
+            self.column = 1;
+        } else if char != '\r' {
+            self.column += 1;
+        }
+
+        Some(char)
     }
 
-    fn back(&mut self) {
-        self.index -= 1;
+    /// Consume the next char if it is `char`.
+    fn eat(&mut self, char: char) -> bool {
+        let is_char = self.peek() == Some(char);
+
+        if is_char {
+            self.next();
+        }
+
+        is_char
     }
 
-    fn get(&self, range: Range<usize>) -> String {
-        self.code[range]
-            .iter()
-            .map(|(_, char)| char.to_string())
-            .collect::<Vec<String>>()
-            .join("")
+    /// Consume the chars until just after `end`, returns `false` if the code ends before.
+    fn skip_past(&mut self, end: &str) -> bool {
+        match self.code[self.index..].find(end) {
+            Some(offset) => {
+                let end_index = self.index + offset + end.len();
+
+                while self.index < end_index {
+                    self.next();
+                }
+
+                true
+            }
+            None => false,
+        }
     }
 
-    pub async fn lex(mut self) -> TokenArray {
-        let mut tokens = TokenArray::with_capacity(1000);
+    fn error(&self, span: Span, message: &str) -> FormatError {
+        FormatError {
+            line: span.line,
+            column: span.column,
+            message: message.to_string(),
+        }
+    }
 
+    /// Consume a quoted literal up to its closing `quote`, returns `false` if the line ends before.
+    fn quoted(&mut self, quote: char) -> bool {
         loop {
-            yield_now().await;
-
-            if let Some((index, char)) = self.next() {
-                let mut token = Token::Unknown(char);
-
-                match char {
-                    '\0' => token = Token::EndOfFile,
-
-                    '(' => token = Token::LeftParenthesis,
-                    ')' => token = Token::RightParenthesis,
-                    '{' => token = Token::LeftBrace,
-                    '}' => token = Token::RightBrace,
-                    '[' => token = Token::LeftBracket,
-                    ']' => token = Token::RightBracket,
-
-                    '+' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::PlusEqual,
-                            Some((_, '+')) => Token::Increment,
-                            Some(_) => {
-                                self.back();
-                                Token::Plus
-                            }
-                            None => return tokens,
-                        }
+            match self.peek() {
+                Some(char) if char == quote => {
+                    self.next();
+                    return true;
+                }
+                Some('\\') => {
+                    self.next();
+
+                    // An escaped newline continues the literal on the next line
+                    if self.eat('\r') {
+                        self.eat('\n');
+                    } else {
+                        self.next();
                     }
-                    '-' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::MinusEqual,
-                            Some((_, '-')) => Token::Decrement,
-                            Some((_, '>')) => Token::Arrow,
-                            Some(_) => {
-                                self.back();
-                                Token::Minus
-                            }
-                            None => return tokens,
-                        }
+                }
+                Some('\n' | '\r') | None => return false,
+                Some(_) => {
+                    self.next();
+                }
+            }
+        }
+    }
+
+    /// Consume a raw string literal, the next char being its opening quote.
+    fn raw_string(&mut self) -> bool {
+        self.next();
+
+        let delimiter_start = self.index;
+
+        loop {
+            match self.next() {
+                Some('(') => break,
+                Some(' ' | ')' | '\\' | '\t' | '\n' | '\r') | None => return false,
+                Some(_) => {}
+            }
+        }
+
+        let end = ")".to_string() + &self.code[delimiter_start..self.index - 1] + "\"";
+
+        self.skip_past(&end)
+    }
+
+    pub fn lex(mut self) -> Result<TokenArray<'a>, FormatError> {
+        let mut token_array = TokenArray::default();
+        let mut is_directive = false;
+
+        while let Some(char) = self.next() {
+            let start = self.index - char.len_utf8();
+            let mut span = Span {
+                start,
+                end: start,
+                line: self.line,
+                column: self.column - 1,
+            };
+
+            // `next` already moved to the next line
+            if char == '\n' || char == '\r' {
+                span.line -= 1;
+                span.column = self.code[..start]
+                    .rfind(['\n', '\r'])
+                    .map_or(start, |index| start - index - 1)
+                    + 1;
+            }
+
+            let token = match char {
+                '\0' => Token::EndOfFile,
+
+                '(' => Token::LeftParenthesis,
+                ')' => Token::RightParenthesis,
+                '{' => Token::LeftBrace,
+                '}' => Token::RightBrace,
+                '[' if self.eat('[') => {
+                    if !self.skip_past("]]") {
+                        return Err(self.error(span, "unterminated attribute"));
                     }
-                    '*' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::TimesEqual,
-                            Some(_) => {
-                                self.back();
-                                Token::Times
-                            }
-                            None => return tokens,
-                        }
+
+                    Token::Attribute(&self.code[start + 2..self.index - 2])
+                }
+                '[' => Token::LeftBracket,
+                ']' => Token::RightBracket,
+
+                '+' if self.eat('=') => Token::PlusEqual,
+                '+' if self.eat('+') => Token::Increment,
+                '+' => Token::Plus,
+                '-' if self.eat('=') => Token::MinusEqual,
+                '-' if self.eat('-') => Token::Decrement,
+                '-' if self.eat('>') => Token::Arrow,
+                '-' => Token::Minus,
+                '*' if self.eat('=') => Token::TimesEqual,
+                '*' => Token::Times,
+                '%' if self.eat('=') => Token::ModuloEqual,
+                '%' => Token::Modulo,
+                '/' if self.eat('=') => Token::SlashEqual,
+                '/' if self.eat('/') => {
+                    while !matches!(self.peek(), Some('\n' | '\r') | None) {
+                        self.next();
                     }
-                    '%' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::ModuloEqual,
-                            Some(_) => {
-                                self.back();
-                                Token::Modulo
-                            }
-                            None => return tokens,
-                        }
+
+                    Token::SimpleComment(&self.code[start + 2..self.index])
+                }
+                '/' if self.eat('*') => {
+                    if !self.skip_past("*/") {
+                        return Err(self.error(span, "unterminated comment"));
                     }
-                    '/' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::SlashEqual,
-                            Some((_, '/')) => {
-                                let start_index = index;
-
-                                loop {
-                                    if let Some((index, char)) = self.next() {
-                                        if let '\n' = char {
-                                            self.back();
-                                            break Token::SimpleComment(
-                                                self.get(start_index + 2..index),
-                                            );
-                                        }
-                                    } else {
-                                        return tokens;
-                                    }
-                                }
-                            }
-                            Some((_, '*')) => {
-                                let start_index = index;
-
-                                loop {
-                                    if let Some((_, char)) = self.next() {
-                                        if let '*' = char {
-                                            if let Some((index, '/')) = self.next() {
-                                                break Token::MultilineComment(
-                                                    self.get(start_index + 2..index - 1),
-                                                );
-                                            } else {
-                                                self.back();
-                                            }
-                                        }
-                                    } else {
-                                        return tokens;
-                                    }
-                                }
-                            }
-                            Some(_) => {
-                                self.back();
-                                Token::Slash
-                            }
-                            None => return tokens,
-                        }
+
+                    Token::MultilineComment(&self.code[start + 2..self.index - 2])
+                }
+                '/' => Token::Slash,
+                '?' => Token::QuestionMark,
+                '&' if self.eat('=') => Token::AmpersandEqual,
+                '&' if self.eat('&') => Token::And,
+                '&' => Token::Ampersand,
+                '^' if self.eat('=') => Token::CaretEqual,
+                '^' => Token::Caret,
+                '|' if self.eat('=') => Token::PipeEqual,
+                '|' if self.eat('|') => Token::Or,
+                '|' => Token::Pipe,
+                '~' => Token::Tilde,
+
+                ':' if self.eat(':') => Token::ScopeResolution,
+                ':' => Token::Colon,
+                ';' => Token::SemiColon,
+                ',' => Token::Comma,
+                '.' => Token::Period,
+
+                '=' if self.eat('=') => Token::EqualEqual,
+                '=' => Token::Equal,
+                '>' if self.eat('=') => Token::GreaterEqual,
+                '>' if self.eat('>') => {
+                    if self.eat('=') {
+                        Token::RightShiftEqual
+                    } else {
+                        Token::RightShift
                     }
-                    '?' => token = Token::QuestionMark,
-                    '&' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::AmpersandEqual,
-                            Some((_, '&')) => Token::And,
-                            Some(_) => {
-                                self.back();
-                                Token::Ampersand
-                            }
-                            None => return tokens,
-                        }
+                }
+                '>' => Token::Greater,
+                '<' if self.eat('=') => Token::LessEqual,
+                '<' if self.eat('<') => {
+                    if self.eat('=') {
+                        Token::LeftShiftEqual
+                    } else {
+                        Token::LeftShift
                     }
-                    '^' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::CaretEqual,
-                            Some(_) => {
-                                self.back();
-                                Token::Caret
-                            }
-                            None => return tokens,
+                }
+                '<' => Token::Less,
+                '!' if self.eat('=') => Token::NotEqual,
+                '!' => Token::Not,
+
+                '\n' => Token::Newline,
+                '\r' => {
+                    self.eat('\n');
+                    Token::Newline
+                }
+                ' ' => Token::Space,
+                '\t' => Token::Tab,
+
+                '"' | '\'' => {
+                    let is_terminated = self.quoted(char);
+                    let content = &self.code[start + 1..self.index - is_terminated as usize];
+
+                    match (char, is_terminated) {
+                        ('"', true) => Token::StringValue(content),
+                        ('\'', true) => Token::CharValue(content),
+                        // Directives can hold any text, like `#error don't`
+                        (_, false) if is_directive => {
+                            self.index = start + 1;
+                            self.line = span.line;
+                            self.column = span.column + 1;
+
+                            Token::Unknown(char)
                         }
+                        ('"', false) => return Err(self.error(span, "unterminated string")),
+                        _ => return Err(self.error(span, "unterminated character")),
                     }
-                    '|' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::PipeEqual,
-                            Some((_, '|')) => Token::Or,
-                            Some(_) => {
-                                self.back();
-                                Token::Pipe
-                            }
-                            None => return tokens,
-                        }
+                }
+
+                '#' => {
+                    // The directive name can be indented after the hash
+                    while matches!(self.peek(), Some(' ' | '\t')) {
+                        self.next();
                     }
-                    '~' => token = Token::Tilde,
-
-                    ':' => {
-                        token = match self.next() {
-                            Some((_, ':')) => Token::ScopeResolution,
-                            Some(_) => {
-                                self.back();
-                                Token::Colon
-                            }
-                            None => return tokens,
-                        }
+
+                    while self.peek().is_some_and(is_letter) {
+                        self.next();
                     }
-                    ';' => token = Token::SemiColon,
-                    ',' => token = Token::Comma,
-                    '.' => token = Token::Period,
-
-                    '=' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::EqualEqual,
-                            Some(_) => {
-                                self.back();
-                                Token::Equal
-                            }
-                            None => return tokens,
-                        }
+
+                    Token::Macro(&self.code[start + 1..self.index])
+                }
+
+                _ if is_letter(char) || char == '_' => {
+                    while self
+                        .peek()
+                        .is_some_and(|char| is_letter(char) || is_numeric(char) || char == '_')
+                    {
+                        self.next();
                     }
-                    '>' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::GreaterEqual,
-                            Some((_, '>')) => match self.next() {
-                                Some((_, '=')) => Token::RightShiftEqual,
-                                Some(_) => {
-                                    self.back();
-                                    Token::RightShift
-                                }
-                                None => return tokens,
-                            },
-                            Some(_) => {
-                                self.back();
-                                Token::Greater
+
+                    let content = &self.code[start..self.index];
+
+                    match (content, self.peek()) {
+                        ("R" | "LR" | "uR" | "UR" | "u8R", Some('"')) => {
+                            if !self.raw_string() {
+                                return Err(self.error(span, "unterminated raw string"));
                             }
-                            None => return tokens,
+
+                            Token::RawStringValue(&self.code[start..self.index])
                         }
-                    }
-                    '<' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::LessEqual,
-                            Some((_, '<')) => match self.next() {
-                                Some((_, '=')) => Token::LeftShiftEqual,
-                                Some(_) => {
-                                    self.back();
-                                    Token::LeftShift
-                                }
-                                None => return tokens,
-                            },
-                            Some(_) => {
-                                self.back();
-                                Token::Less
-                            }
-                            None => return tokens,
+                        ("L" | "u" | "U" | "u8", Some('"' | '\'')) => {
+                            Token::EncodingPrefix(content)
                         }
+                        _ => keyword(content),
                     }
-                    '!' => {
-                        token = match self.next() {
-                            Some((_, '=')) => Token::NotEqual,
-                            Some(_) => {
-                                self.back();
-                                Token::Not
-                            }
-                            None => return tokens,
+                }
+                _ if is_numeric(char) => {
+                    let mut previous_char = char;
+
+                    // Numbers also include their prefix, suffix, exponent and digit separators
+                    while let Some(char) = self.peek() {
+                        let is_exponent_sign = matches!(char, '+' | '-')
+                            && matches!(previous_char, 'e' | 'E' | 'p' | 'P');
+                        let is_separator = char == '\''
+                            && self.code[self.index + 1..]
+                                .chars()
+                                .next()
+                                .is_some_and(|char| char.is_ascii_alphanumeric());
+
+                        if !char.is_ascii_alphanumeric()
+                            && !matches!(char, '.' | '_')
+                            && !is_exponent_sign
+                            && !is_separator
+                        {
+                            break;
                         }
-                    }
 
-                    '\n' => token = Token::Newline,
-                    ' ' => token = Token::Space,
-                    '\t' => token = Token::Tab,
-
-                    '"' => {
-                        let start_index = index;
-
-                        loop {
-                            if let Some((index, char)) = self.next() {
-                                match char {
-                                    '"' => {
-                                        token =
-                                            Token::StringValue(self.get(start_index + 1..index));
-                                        break;
-                                    }
-                                    '\\' => {
-                                        self.skip();
-                                    }
-                                    _ => {}
-                                }
-                            } else {
-                                return tokens;
-                            }
-                        }
+                        self.next();
+                        previous_char = char;
                     }
-                    '\'' => {
-                        let start_index = index;
-
-                        loop {
-                            if let Some((index, char)) = self.next() {
-                                match char {
-                                    '\'' => {
-                                        token = Token::CharValue(self.get(start_index + 1..index));
-                                        break;
-                                    }
-                                    '\\' => {
-                                        self.skip();
-                                    }
-                                    _ => {}
-                                }
-                            } else {
-                                return tokens;
-                            }
-                        }
+
+                    let content = &self.code[start..self.index];
+                    let is_hexadecimal = content.starts_with("0x") || content.starts_with("0X");
+                    let is_float = content.contains('.')
+                        || if is_hexadecimal {
+                            content.contains(['p', 'P'])
+                        } else {
+                            content.contains(['e', 'E'])
+                        };
+
+                    if is_float {
+                        Token::FloatValue(content)
+                    } else {
+                        Token::DigitValue(content)
                     }
+                }
+                _ => Token::Unknown(char),
+            };
 
-                    '#' => {
-                        let start_index = index;
-                        let mut end_index;
+            match token {
+                Token::Macro(_) => is_directive = true,
+                Token::Newline if token_array.tokens.last() != Some(&Token::Unknown('\\')) => {
+                    is_directive = false
+                }
+                _ => {}
+            }
 
-                        loop {
-                            if let Some((index, char)) = self.next() {
-                                end_index = index;
+            span.end = self.index;
+            token_array.tokens.push(token);
+            token_array.spans.push(span);
 
-                                if !is_letter(char) {
-                                    self.back();
-                                    break;
-                                }
-                            } else {
-                                return tokens;
-                            }
-                        }
+            if token == Token::EndOfFile {
+                break;
+            }
+        }
 
-                        token = Token::Macro(self.get(start_index + 1..end_index));
-                    }
+        match_angles(&mut token_array);
 
-                    _ => {
-                        if is_letter(char) || char == '_' {
-                            let start_index = index;
-                            let mut end_index;
-
-                            loop {
-                                if let Some((index, char)) = self.next() {
-                                    end_index = index;
-
-                                    if !is_letter(char) && !is_numeric(char) && char != '_' {
-                                        self.back();
-                                        break;
-                                    }
-                                } else {
-                                    return tokens;
-                                }
-                            }
+        Ok(token_array)
+    }
+}
 
-                            let content = self.get(start_index..end_index);
-
-                            match content.as_str() {
-                                "if" => token = Token::If,
-                                "else" => token = Token::Else,
-                                "for" => token = Token::For,
-                                "while" => token = Token::While,
-                                "return" => token = Token::Return,
-                                "do" => token = Token::Do,
-                                "new" => token = Token::New,
-                                "delete" => token = Token::Delete,
-                                "null" => token = Token::Null,
-                                "asm" => token = Token::Asm,
-                                "double" => token = Token::Double,
-                                "switch" => token = Token::Switch,
-                                "auto" => token = Token::Auto,
-                                "operator" => token = Token::Operator,
-                                "template" => token = Token::Template,
-                                "break" => token = Token::Break,
-                                "enum" => token = Token::Enum,
-                                "private" => token = Token::Private,
-                                "this" => token = Token::This,
-                                "case" => token = Token::Case,
-                                "extern" => token = Token::Extern,
-                                "protected" => token = Token::Protected,
-                                "throw" => token = Token::Throw,
-                                "catch" => token = Token::Catch,
-                                "float" => token = Token::Float,
-                                "public" => token = Token::Public,
-                                "try" => token = Token::Try,
-                                "char" => token = Token::Char,
-                                "register" => token = Token::Register,
-                                "typedef" => token = Token::Typedef,
-                                "class" => token = Token::Class,
-                                "friend" => token = Token::Friend,
-                                "union" => token = Token::Union,
-                                "const" => token = Token::Const,
-                                "goto" => token = Token::Goto,
-                                "short" => token = Token::Short,
-                                "unsigned" => token = Token::Unsigned,
-                                "continue" => token = Token::Continue,
-                                "signed" => token = Token::Signed,
-                                "virtual" => token = Token::Virtual,
-                                "default" => token = Token::Default,
-                                "inline" => token = Token::Inline,
-                                "sizeof" => token = Token::Sizeof,
-                                "void" => token = Token::Void,
-                                "int" => token = Token::Int,
-                                "static" => token = Token::Static,
-                                "volatile" => token = Token::Volatile,
-                                "long" => token = Token::Long,
-                                "struct" => token = Token::Struct,
-                                "namespace" => token = Token::Namespace,
-                                "true" | "false" => token = Token::BoolValue(content),
-                                _ => token = Token::Identifier(content),
-                            }
-                        } else if is_numeric(char) {
-                            let start_index = index;
-                            let mut end_index;
-                            let mut is_float = false;
-
-                            loop {
-                                if let Some((index, char)) = self.next() {
-                                    end_index = index;
-
-                                    if char == '.' {
-                                        is_float = true;
-                                    }
-
-                                    if !is_numeric(char) && (!is_float || char != '.') {
-                                        self.back();
-                                        break;
-                                    }
-                                } else {
-                                    return tokens;
-                                }
-                            }
+/// Turn the `<` and `>` around template arguments into angle brackets.
+///
+/// A `<` opens template arguments when it follows a name or `template` and is closed before the end
+/// of the statement, without any logical operator in between. A `>>` closing two template argument
+/// lists is split in two.
+fn match_angles(token_array: &mut TokenArray) {
+    let TokenArray { tokens, spans } = token_array;
+    let is_whitespace = |token: &Token| matches!(token, Token::Space | Token::Tab | Token::Newline);
+    let mut is_directive = false;
+    let mut in_directive = Vec::with_capacity(tokens.len());
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Macro(_) => is_directive = true,
+            Token::Newline if index == 0 || tokens[index - 1] != Token::Unknown('\\') => {
+                is_directive = false
+            }
+            _ => {}
+        }
 
-                            let content = self.get(start_index..end_index);
+        in_directive.push(is_directive);
+    }
 
-                            if is_float {
-                                token = Token::FloatValue(content);
-                            } else {
-                                token = Token::DigitValue(content);
-                            }
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let follows_name = tokens[..index]
+            .iter()
+            .rev()
+            .find(|token| !is_whitespace(token))
+            .is_some_and(|token| matches!(token, Token::Identifier(_) | Token::Template));
+
+        if tokens[index] == Token::Less && follows_name {
+            let mut depth = 1;
+            let mut parenthesis_depth = 0usize;
+            let mut closing = None;
+
+            for (closing_index, token) in tokens.iter().enumerate().skip(index + 1) {
+                if in_directive[closing_index] != in_directive[index] {
+                    break;
+                }
+
+                match token {
+                    Token::LeftParenthesis | Token::LeftBracket => parenthesis_depth += 1,
+                    Token::RightParenthesis | Token::RightBracket => {
+                        if parenthesis_depth == 0 {
+                            break;
                         }
+
+                        parenthesis_depth -= 1;
                     }
-                }
+                    _ if parenthesis_depth > 0 => {}
+                    Token::Less | Token::LeftAngle => depth += 1,
+                    Token::Greater | Token::RightAngle => {
+                        depth -= 1;
+
+                        if depth == 0 {
+                            closing = Some((closing_index, false));
+                            break;
+                        }
+                    }
+                    Token::RightShift if depth >= 2 => {
+                        depth -= 2;
 
-                tokens.push(token);
-            } else {
-                break;
+                        if depth == 0 {
+                            closing = Some((closing_index, true));
+                            break;
+                        }
+                    }
+                    Token::SemiColon
+                    | Token::LeftBrace
+                    | Token::RightBrace
+                    | Token::And
+                    | Token::Or
+                    | Token::QuestionMark
+                    | Token::Colon
+                    | Token::RightShift
+                    | Token::GreaterEqual
+                    | Token::RightShiftEqual
+                    | Token::EndOfFile => break,
+                    _ => {}
+                }
             }
 
-            if let Token::EndOfFile = tokens.last().unwrap() {
-                break;
+            if let Some((closing_index, is_shift)) = closing {
+                tokens[index] = Token::LeftAngle;
+                tokens[closing_index] = Token::RightAngle;
+
+                if is_shift {
+                    let span = spans[closing_index];
+
+                    spans[closing_index].end -= 1;
+                    tokens.insert(closing_index + 1, Token::RightAngle);
+                    spans.insert(
+                        closing_index + 1,
+                        Span {
+                            start: span.start + 1,
+                            column: span.column + 1,
+                            ..span
+                        },
+                    );
+                    in_directive.insert(closing_index + 1, in_directive[closing_index]);
+                }
             }
         }
 
-        tokens
+        index += 1;
     }
 }
 
-fn is_letter(char: char) -> bool {
-    match char {
-        'a'..='z' | 'A'..='Z' => true,
-        _ => false,
+/// Keyword or identifier named `content`.
+fn keyword(content: &str) -> Token<'_> {
+    match content {
+        "if" => Token::If,
+        "else" => Token::Else,
+        "for" => Token::For,
+        "while" => Token::While,
+        "return" => Token::Return,
+        "do" => Token::Do,
+        "new" => Token::New,
+        "delete" => Token::Delete,
+        "null" => Token::Null,
+        "asm" => Token::Asm,
+        "double" => Token::Double,
+        "switch" => Token::Switch,
+        "auto" => Token::Auto,
+        "operator" => Token::Operator,
+        "template" => Token::Template,
+        "break" => Token::Break,
+        "enum" => Token::Enum,
+        "private" => Token::Private,
+        "this" => Token::This,
+        "case" => Token::Case,
+        "extern" => Token::Extern,
+        "protected" => Token::Protected,
+        "throw" => Token::Throw,
+        "catch" => Token::Catch,
+        "float" => Token::Float,
+        "public" => Token::Public,
+        "try" => Token::Try,
+        "char" => Token::Char,
+        "register" => Token::Register,
+        "typedef" => Token::Typedef,
+        "class" => Token::Class,
+        "friend" => Token::Friend,
+        "union" => Token::Union,
+        "const" => Token::Const,
+        "goto" => Token::Goto,
+        "short" => Token::Short,
+        "unsigned" => Token::Unsigned,
+        "continue" => Token::Continue,
+        "signed" => Token::Signed,
+        "virtual" => Token::Virtual,
+        "default" => Token::Default,
+        "inline" => Token::Inline,
+        "sizeof" => Token::Sizeof,
+        "void" => Token::Void,
+        "int" => Token::Int,
+        "static" => Token::Static,
+        "volatile" => Token::Volatile,
+        "long" => Token::Long,
+        "struct" => Token::Struct,
+        "namespace" => Token::Namespace,
+        "typename" => Token::Typename,
+        "using" => Token::Using,
+        "constexpr" => Token::Constexpr,
+        "decltype" => Token::Decltype,
+        "explicit" => Token::Explicit,
+        "mutable" => Token::Mutable,
+        "noexcept" => Token::Noexcept,
+        "nullptr" => Token::Nullptr,
+        "static_assert" => Token::StaticAssert,
+        "alignof" => Token::Alignof,
+        "true" | "false" => Token::BoolValue(content),
+        _ => Token::Identifier(content),
     }
 }
 
+/// Whether `token` is a whitespace or a comment, which the code doesn't depend on.
+pub fn is_blank(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Space
+            | Token::Tab
+            | Token::Newline
+            | Token::SimpleComment(_)
+            | Token::MultilineComment(_)
+    )
+}
+
+fn is_letter(char: char) -> bool {
+    char.is_ascii_alphabetic()
+}
+
 fn is_numeric(char: char) -> bool {
-    match char {
-        '0'..='9' => true,
-        _ => false,
+    char.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(code: &str) -> TokenArray<'_> {
+        Tokenizer::new(code).lex().unwrap()
+    }
+
+    fn lex_error(code: &str) -> (usize, usize, String) {
+        let error = Tokenizer::new(code).lex().unwrap_err();
+
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn spans_tokens() {
+        let token_array = lex("a = bc;\n  d");
+
+        assert_eq!(
+            token_array.tokens,
+            [
+                Token::Identifier("a"),
+                Token::Space,
+                Token::Equal,
+                Token::Space,
+                Token::Identifier("bc"),
+                Token::SemiColon,
+                Token::Newline,
+                Token::Space,
+                Token::Space,
+                Token::Identifier("d"),
+            ]
+        );
+        assert_eq!(
+            token_array.spans[4],
+            Span {
+                start: 4,
+                end: 6,
+                line: 1,
+                column: 5,
+            }
+        );
+        assert_eq!(
+            token_array.spans[6],
+            Span {
+                start: 7,
+                end: 8,
+                line: 1,
+                column: 8,
+            }
+        );
+        assert_eq!(
+            token_array.spans[9],
+            Span {
+                start: 10,
+                end: 11,
+                line: 2,
+                column: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn spans_crlf_as_one_newline() {
+        let token_array = lex("a\r\nb");
+
+        assert_eq!(
+            token_array.tokens,
+            [
+                Token::Identifier("a"),
+                Token::Newline,
+                Token::Identifier("b")
+            ]
+        );
+        assert_eq!(token_array.spans[1].end, 3);
+        assert_eq!(
+            (token_array.spans[2].line, token_array.spans[2].column),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn lexes_multiline_comment() {
+        let token_array = lex("/* a\n * b */x");
+
+        assert_eq!(
+            token_array.tokens,
+            [Token::MultilineComment(" a\n * b "), Token::Identifier("x")]
+        );
+        assert_eq!(
+            token_array.spans[0],
+            Span {
+                start: 0,
+                end: 12,
+                line: 1,
+                column: 1,
+            }
+        );
+        assert_eq!(
+            (token_array.spans[1].line, token_array.spans[1].column),
+            (2, 8)
+        );
+    }
+
+    #[test]
+    fn tells_scope_resolution_from_colon() {
+        assert_eq!(
+            lex("a::b ? c : ::d").tokens,
+            [
+                Token::Identifier("a"),
+                Token::ScopeResolution,
+                Token::Identifier("b"),
+                Token::Space,
+                Token::QuestionMark,
+                Token::Space,
+                Token::Identifier("c"),
+                Token::Space,
+                Token::Colon,
+                Token::Space,
+                Token::ScopeResolution,
+                Token::Identifier("d"),
+            ]
+        );
+        assert_eq!(lex(":::").tokens, [Token::ScopeResolution, Token::Colon]);
+    }
+
+    #[test]
+    fn fails_on_unterminated_string() {
+        assert_eq!(
+            lex_error("a = \"b;\nc"),
+            (1, 5, "unterminated string".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_on_unterminated_comment() {
+        assert_eq!(
+            lex_error("a;\n  /* b\n c"),
+            (2, 3, "unterminated comment".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_unterminated_quote_of_directive() {
+        assert_eq!(
+            lex("#error don't").tokens[..3],
+            [
+                Token::Macro("error"),
+                Token::Space,
+                Token::Identifier("don")
+            ]
+        );
     }
 }
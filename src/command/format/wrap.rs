@@ -0,0 +1,321 @@
+use crate::config::format::ArgumentsLayout;
+
+use super::{
+    lexer::{Token, TokenArray, Tokenizer},
+    FormatOptions,
+};
+
+/// Break the lines of `code` that are longer than `max_width`.
+///
+/// Lines are broken after the commas inside parentheses, braces or template arguments, or before
+/// the binary operators, choosing the least nested ones first. Continuation lines are indented one
+/// more level.
+pub fn wrap(code: String, options: &FormatOptions) -> String {
+    if options.max_width == 0 {
+        return code;
+    }
+
+    // Never wrap code the lexer did not fully understand
+    let Ok(TokenArray { tokens, .. }) = Tokenizer::new(&code).lex() else {
+        return code;
+    };
+
+    let mut wrapped_code = Vec::new();
+    let mut is_continuation = false;
+
+    for line in tokens.split(|token| *token == Token::Newline) {
+        let is_directive = is_continuation;
+
+        is_continuation = trim(line).last() == Some(&Token::Unknown('\\'));
+
+        let indent_length = line
+            .iter()
+            .take_while(|token| matches!(token, Token::Space | Token::Tab))
+            .count();
+        let indent = render(&line[..indent_length]);
+        let line = trim(&line[indent_length..]);
+
+        if line.is_empty() {
+            wrapped_code.push(String::new());
+            continue;
+        }
+
+        if is_directive
+            || matches!(line[0], Token::Macro(_))
+            || line.iter().any(|token| token.to_string().contains('\n'))
+        {
+            wrapped_code.push(indent + &render(line));
+            continue;
+        }
+
+        let mut pieces = Vec::new();
+
+        break_line(line, 0, &indent, options, &mut pieces);
+
+        for (level, piece) in pieces {
+            wrapped_code.push(indent.clone() + &options.tab.repeat(level) + &render(piece));
+        }
+    }
+
+    wrapped_code.join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum BreakKind {
+    Comma,
+    Logical,
+    Binary,
+    Assignment,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Break {
+    index: usize,
+    depth: usize,
+    kind: BreakKind,
+}
+
+fn break_line<'a, 'b>(
+    tokens: &'a [Token<'b>],
+    level: usize,
+    indent: &str,
+    options: &FormatOptions,
+    pieces: &mut Vec<(usize, &'a [Token<'b>])>,
+) {
+    let tokens = trim(tokens);
+    let fits = |level: usize, tokens: &[Token]| {
+        width(indent, options)
+            + width(&options.tab, options) * level
+            + width(&render(tokens), options)
+            <= options.max_width
+    };
+
+    if fits(level, tokens) {
+        pieces.push((level, tokens));
+        return;
+    }
+
+    let breaks = find_breaks(tokens);
+    let Some(depth) = breaks.iter().map(|r#break| r#break.depth).min() else {
+        pieces.push((level, tokens));
+        return;
+    };
+    let kind = breaks
+        .iter()
+        .filter(|r#break| r#break.depth == depth)
+        .map(|r#break| r#break.kind)
+        .min()
+        .unwrap();
+    let mut positions: Vec<usize> = breaks
+        .into_iter()
+        .filter(|r#break| r#break.depth == depth && r#break.kind == kind)
+        .map(|r#break| r#break.index)
+        .collect();
+    let mut lines: Vec<(usize, &[Token])> = Vec::new();
+
+    if kind == BreakKind::Comma {
+        // Only the first argument list at this depth is broken, the rest is handled recursively
+        let opening = opening_index(tokens, positions[0]);
+        let closing = closing_index(tokens, opening);
+
+        positions.retain(|index| *index < closing);
+        positions.insert(0, opening + 1);
+    }
+
+    if kind == BreakKind::Comma && options.arguments == ArgumentsLayout::OnePerLine {
+        let arguments = &tokens[positions[0]..];
+
+        lines.push((level, &tokens[..positions[0]]));
+
+        if fits(level + 1, arguments) {
+            lines.push((level + 1, arguments));
+        } else {
+            for (index, start) in positions.iter().enumerate() {
+                let end = positions.get(index + 1).copied().unwrap_or(tokens.len());
+
+                lines.push((level + 1, &tokens[*start..end]));
+            }
+        }
+    } else {
+        let mut line_start = 0;
+        let mut line_level = level;
+
+        for (index, start) in positions.iter().enumerate() {
+            let end = positions.get(index + 1).copied().unwrap_or(tokens.len());
+
+            if *start > line_start && !fits(line_level, &tokens[line_start..end]) {
+                lines.push((line_level, &tokens[line_start..*start]));
+                line_start = *start;
+                line_level = level + 1;
+            }
+        }
+
+        lines.push((line_level, &tokens[line_start..]));
+    }
+
+    for (line_level, line) in lines {
+        let line = trim(line);
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.len() == tokens.len() {
+            pieces.push((line_level, line));
+        } else {
+            break_line(line, line_level, indent, options, pieces);
+        }
+    }
+}
+
+/// Find where the line can be broken, a break at `index` means the token at `index` starts a new line.
+fn find_breaks(tokens: &[Token]) -> Vec<Break> {
+    let mut breaks = Vec::new();
+    let mut depth = 0usize;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LeftParenthesis | Token::LeftBracket | Token::LeftBrace | Token::LeftAngle => {
+                depth += 1
+            }
+            Token::RightParenthesis
+            | Token::RightBracket
+            | Token::RightBrace
+            | Token::RightAngle => depth = depth.saturating_sub(1),
+            Token::Comma if depth > 0 && index + 1 < tokens.len() => breaks.push(Break {
+                index: index + 1,
+                depth,
+                kind: BreakKind::Comma,
+            }),
+            _ => {
+                let is_spaced = index > 0
+                    && index + 1 < tokens.len()
+                    && tokens[index - 1] == Token::Space
+                    && tokens[index + 1] == Token::Space;
+
+                if !is_spaced {
+                    continue;
+                }
+
+                match token {
+                    Token::Or | Token::And => breaks.push(Break {
+                        index,
+                        depth,
+                        kind: BreakKind::Logical,
+                    }),
+                    Token::Plus
+                    | Token::Minus
+                    | Token::Times
+                    | Token::Slash
+                    | Token::Modulo
+                    | Token::Ampersand
+                    | Token::Caret
+                    | Token::Pipe
+                    | Token::LeftShift
+                    | Token::RightShift
+                    | Token::EqualEqual
+                    | Token::NotEqual
+                    | Token::Less
+                    | Token::Greater
+                    | Token::LessEqual
+                    | Token::GreaterEqual => breaks.push(Break {
+                        index,
+                        depth,
+                        kind: BreakKind::Binary,
+                    }),
+                    Token::Equal
+                    | Token::PlusEqual
+                    | Token::MinusEqual
+                    | Token::TimesEqual
+                    | Token::SlashEqual
+                    | Token::ModuloEqual
+                    | Token::AmpersandEqual
+                    | Token::CaretEqual
+                    | Token::PipeEqual
+                    | Token::LeftShiftEqual
+                    | Token::RightShiftEqual => breaks.push(Break {
+                        index: index + 1,
+                        depth,
+                        kind: BreakKind::Assignment,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    breaks
+}
+
+fn opening_index(tokens: &[Token], index: usize) -> usize {
+    let mut depth = 0usize;
+
+    for index in (0..index).rev() {
+        match tokens[index] {
+            Token::RightParenthesis
+            | Token::RightBracket
+            | Token::RightBrace
+            | Token::RightAngle => depth += 1,
+            Token::LeftParenthesis | Token::LeftBracket | Token::LeftBrace | Token::LeftAngle => {
+                if depth == 0 {
+                    return index;
+                }
+
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+
+    0
+}
+
+fn closing_index(tokens: &[Token], opening: usize) -> usize {
+    let mut depth = 0usize;
+
+    for (index, token) in tokens.iter().enumerate().skip(opening) {
+        match token {
+            Token::LeftParenthesis | Token::LeftBracket | Token::LeftBrace | Token::LeftAngle => {
+                depth += 1
+            }
+            Token::RightParenthesis
+            | Token::RightBracket
+            | Token::RightBrace
+            | Token::RightAngle => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tokens.len()
+}
+
+fn trim<'a, 'b>(tokens: &'a [Token<'b>]) -> &'a [Token<'b>] {
+    let is_whitespace = |token: &Token| matches!(token, Token::Space | Token::Tab);
+    let start = tokens
+        .iter()
+        .position(|token| !is_whitespace(token))
+        .unwrap_or(tokens.len());
+    let end = tokens
+        .iter()
+        .rposition(|token| !is_whitespace(token))
+        .map_or(start, |index| index + 1);
+
+    &tokens[start..end]
+}
+
+fn render(tokens: &[Token]) -> String {
+    tokens.iter().map(Token::to_string).collect()
+}
+
+/// Width of `code` in columns, tabulations being `tab_size` wide.
+pub fn width(code: &str, options: &FormatOptions) -> usize {
+    code.chars()
+        .map(|char| if char == '\t' { options.tab_size } else { 1 })
+        .sum()
+}
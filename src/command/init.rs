@@ -14,10 +14,21 @@ pub fn init(path: Option<PathBuf>) -> anyhow::Result<()> {
         Repository::init(&project_path).ok();
 
         create_dir(project_path.join("src")).ok();
-        write(project_path.join(".gitignore"), "/.maky\n/obj\n/bin").ok();
+        write(
+            project_path.join(".gitignore"),
+            "/.maky\n/obj\n/bin\n/Maky.local.toml",
+        )
+        .ok();
+        let name = project_path
+            .canonicalize()
+            .unwrap_or(project_path.clone())
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
         write(
             project_path.join("Maky.toml"),
-            "[package]\nversion = \"0.1.0\"",
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\""),
         )
         .ok();
         write(project_path.join("src/main.c"), "#include <stdio.h>\n\nint main()\n{\n\tprintf(\"Hello world !\\n\");\n\n\treturn 0;\n}").ok();
@@ -0,0 +1,650 @@
+use std::{
+    fmt,
+    fs::read_to_string,
+    io::stderr,
+    path::{Path, PathBuf},
+};
+
+use crossterm::{
+    execute,
+    style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
+};
+use glob::glob;
+use hashbrown::{HashMap, HashSet};
+use rayon::prelude::*;
+
+use crate::{
+    config::{
+        lint::{Case, LintConfig, Severity},
+        ProjectConfig,
+    },
+    file::is_header_file,
+};
+
+use super::{
+    format::{
+        find_dir_files,
+        lexer::{is_blank, Span, Token, TokenArray, Tokenizer},
+    },
+    get_project_path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    IncludeGuards,
+    Naming,
+    BannedFunctions,
+    MainHelpers,
+    TodoOwner,
+}
+
+impl Rule {
+    fn severity(&self, lint_config: &LintConfig) -> Severity {
+        match self {
+            Rule::IncludeGuards => lint_config.include_guards,
+            Rule::Naming => lint_config.naming,
+            Rule::BannedFunctions => lint_config.banned_functions,
+            Rule::MainHelpers => lint_config.main_helpers,
+            Rule::TodoOwner => lint_config.todo_owner,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rule::IncludeGuards => "include-guards",
+            Rule::Naming => "naming",
+            Rule::BannedFunctions => "banned-functions",
+            Rule::MainHelpers => "main-helpers",
+            Rule::TodoOwner => "todo-owner",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Lint the project files, or `files` when there are any.
+///
+/// Returns `true` when at least one diagnostic is an error.
+pub fn lint(files: Vec<String>, config_file: String) -> anyhow::Result<bool> {
+    let (project_path, project_config_path) = &get_project_path(&config_file);
+    let mut paths = Vec::new();
+
+    let lint_config = if files.is_empty() {
+        match ProjectConfig::load(project_config_path) {
+            Ok(project_config) => {
+                if let Some(package) = &project_config.package {
+                    let mut explored_path = HashSet::new();
+
+                    for path in package.sources.iter() {
+                        find_dir_files(
+                            &mut paths,
+                            &mut explored_path,
+                            &project_path.join(path),
+                            project_path,
+                            &package.includes,
+                        );
+                    }
+                }
+
+                project_config.lint
+            }
+            Err(error) => return Err(error.into()),
+        }
+    } else {
+        for file in files {
+            for path in glob(&file)? {
+                paths.push(path?);
+            }
+        }
+
+        ProjectConfig::load_without_processing(project_config_path)
+            .map(|project_config| project_config.lint)
+            .unwrap_or_default()
+    };
+
+    paths.sort();
+
+    let results: Vec<(PathBuf, anyhow::Result<Vec<Diagnostic>>)> = paths
+        .into_par_iter()
+        .map(|path| {
+            let diagnostics = lint_file(&path, &lint_config);
+
+            (path, diagnostics)
+        })
+        .collect();
+    let mut error_count = 0;
+    let mut warning_count = 0;
+
+    for (path, diagnostics) in results {
+        let path = path.to_string_lossy();
+        let diagnostics = match diagnostics {
+            Ok(diagnostics) => diagnostics,
+            Err(error) => {
+                error_count += 1;
+
+                execute!(
+                    stderr(),
+                    SetForegroundColor(Color::Red),
+                    Print("Failed to lint : ".bold()),
+                    ResetColor,
+                    Print(format!("{path}:{error}\n")),
+                )?;
+
+                continue;
+            }
+        };
+
+        for diagnostic in diagnostics {
+            let (color, label) = match diagnostic.severity {
+                Severity::Error => {
+                    error_count += 1;
+                    (Color::Red, "Error ")
+                }
+                _ => {
+                    warning_count += 1;
+                    (Color::DarkYellow, "Warning ")
+                }
+            };
+
+            execute!(
+                stderr(),
+                SetForegroundColor(color),
+                Print(label.bold()),
+                ResetColor,
+                Print(format!(
+                    "{path}:{}:{}: {} [{}]\n",
+                    diagnostic.line, diagnostic.column, diagnostic.message, diagnostic.rule
+                )),
+            )?;
+        }
+    }
+
+    if error_count + warning_count > 0 {
+        execute!(
+            stderr(),
+            Print(format!("\n{error_count} error(s) and {warning_count} warning(s)\n").bold()),
+        )?;
+    }
+
+    Ok(error_count > 0)
+}
+
+/// Diagnostics of the enabled rules for the file at `path`, in order, without the ignored ones.
+pub fn lint_file(path: &Path, lint_config: &LintConfig) -> anyhow::Result<Vec<Diagnostic>> {
+    let code = read_to_string(path)?;
+    let token_array = Tokenizer::new(&code).lex()?;
+    let mut linter = Linter {
+        lint_config,
+        tokens: &token_array.tokens,
+        spans: &token_array.spans,
+        diagnostics: Vec::new(),
+    };
+
+    if path.extension().is_some_and(is_header_file) {
+        linter.check_include_guard();
+    }
+
+    linter.check_tokens(code.contains("//@main"));
+    linter.check_comments();
+
+    let ignored_lines = get_ignored_lines(&token_array);
+    let mut diagnostics: Vec<Diagnostic> = linter
+        .diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            ignored_lines
+                .get(&diagnostic.line)
+                .is_none_or(|rules| !rules.is_empty() && !rules.contains(&diagnostic.rule))
+        })
+        .collect();
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+
+    Ok(diagnostics)
+}
+
+/// Lines with a `// maky-lint-ignore` comment, or following it when it's alone on its line.
+///
+/// The comment can list the ignored rules, like `// maky-lint-ignore naming, todo-owner`, and
+/// ignores all of them otherwise.
+fn get_ignored_lines(token_array: &TokenArray) -> HashMap<usize, Vec<Rule>> {
+    let TokenArray { tokens, spans } = token_array;
+    let mut ignored_lines = HashMap::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        let Token::SimpleComment(comment) = token else {
+            continue;
+        };
+        let Some(rules) = comment.trim().strip_prefix("maky-lint-ignore") else {
+            continue;
+        };
+        let rules = rules
+            .split([',', ' '])
+            .filter_map(|name| {
+                [
+                    Rule::IncludeGuards,
+                    Rule::Naming,
+                    Rule::BannedFunctions,
+                    Rule::MainHelpers,
+                    Rule::TodoOwner,
+                ]
+                .into_iter()
+                .find(|rule| rule.to_string() == name)
+            })
+            .collect();
+        let is_trailing = tokens[..index]
+            .iter()
+            .rev()
+            .find(|token| !matches!(token, Token::Space | Token::Tab))
+            .is_some_and(|token| *token != Token::Newline);
+        let line = spans[index].line + !is_trailing as usize;
+
+        ignored_lines.insert(line, rules);
+    }
+
+    ignored_lines
+}
+
+struct Linter<'a> {
+    lint_config: &'a LintConfig,
+    tokens: &'a [Token<'a>],
+    spans: &'a [Span],
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: Rule, index: usize, message: String) {
+        let severity = rule.severity(self.lint_config);
+        let span = self.spans.get(index).copied().unwrap_or_default();
+
+        if severity != Severity::Off {
+            self.diagnostics.push(Diagnostic {
+                rule,
+                severity,
+                line: span.line,
+                column: span.column,
+                message,
+            });
+        }
+    }
+
+    /// Index of the first token after `index` that is neither a whitespace nor a comment.
+    fn next_index(&self, index: usize) -> Option<usize> {
+        (index + 1..self.tokens.len()).find(|index| !is_blank(&self.tokens[*index]))
+    }
+
+    /// Index of the first token before `index` that is neither a whitespace nor a comment.
+    fn previous_index(&self, index: usize) -> Option<usize> {
+        (0..index)
+            .rev()
+            .find(|index| !is_blank(&self.tokens[*index]))
+    }
+
+    fn directive_name(&self, index: usize) -> &str {
+        match self.tokens[index] {
+            Token::Macro(name) => name.trim_start(),
+            _ => "",
+        }
+    }
+
+    /// The header must start with `#pragma once`, or with `#ifndef` and `#define` of the same
+    /// macro and end with `#endif`.
+    fn check_include_guard(&mut self) {
+        let Some(first) = (0..self.tokens.len()).find(|index| !is_blank(&self.tokens[*index]))
+        else {
+            return;
+        };
+
+        if find_pragma_once(self.tokens).is_none() && find_include_guard(self.tokens).is_none() {
+            self.report(
+                Rule::IncludeGuards,
+                first,
+                "header without include guard or `#pragma once`".to_string(),
+            );
+        }
+    }
+
+    /// Check the names, the banned functions and the helper functions of the main files.
+    fn check_tokens(&mut self, is_main_file: bool) {
+        let names = self.lint_config.names.clone();
+        // Whether each open brace is a namespace or an `extern "C"` block, which stay at file level,
+        // and the start of the `typedef` it is part of
+        let mut braces: Vec<(bool, Option<usize>)> = Vec::new();
+        let mut statement_start = 0;
+        let mut parenthesis_depth = 0usize;
+        let mut is_directive = false;
+
+        for index in 0..self.tokens.len() {
+            let token = self.tokens[index];
+
+            match token {
+                Token::Macro(_) => {
+                    is_directive = true;
+
+                    if self.directive_name(index) == "define" {
+                        if let Some(name_index) = self.next_index(index) {
+                            self.check_name(name_index, names.macros, "macro");
+                        }
+                    }
+                }
+                Token::Newline if self.tokens[..index].last() != Some(&Token::Unknown('\\')) => {
+                    is_directive = false
+                }
+                _ => {}
+            }
+
+            if is_directive {
+                continue;
+            }
+
+            let statement = &self.tokens[statement_start..index];
+
+            match token {
+                Token::LeftBrace => {
+                    braces.push((
+                        statement
+                            .iter()
+                            .any(|token| matches!(token, Token::Namespace | Token::Extern)),
+                        statement
+                            .contains(&Token::Typedef)
+                            .then_some(statement_start),
+                    ));
+                    statement_start = index + 1;
+                }
+                Token::RightBrace => {
+                    statement_start = match braces.pop() {
+                        Some((_, Some(typedef_start))) => typedef_start,
+                        _ => index + 1,
+                    };
+                }
+                Token::SemiColon if parenthesis_depth == 0 => {
+                    let declarator_start = statement
+                        .iter()
+                        .rposition(|token| *token == Token::RightBrace)
+                        .map_or(0, |index| index + 1);
+
+                    // `typedef struct { ... } name;` or `typedef int name;`, not function types
+                    if statement.contains(&Token::Typedef)
+                        && !statement[declarator_start..].contains(&Token::LeftParenthesis)
+                    {
+                        if let Some(name_index) = self.previous_index(index) {
+                            self.check_name(name_index, names.types, "type");
+                        }
+                    }
+
+                    statement_start = index + 1;
+                }
+                Token::LeftParenthesis => parenthesis_depth += 1,
+                Token::RightParenthesis => parenthesis_depth = parenthesis_depth.saturating_sub(1),
+                Token::Struct | Token::Class | Token::Union | Token::Enum => {
+                    self.check_type(index, names.types);
+                }
+                Token::Using => {
+                    // `using name = type;`
+                    if let Some(name_index) = self.next_index(index) {
+                        if self
+                            .next_index(name_index)
+                            .is_some_and(|index| self.tokens[index] == Token::Equal)
+                        {
+                            self.check_name(name_index, names.types, "type");
+                        }
+                    }
+                }
+                Token::Identifier(name) => {
+                    let is_call = self
+                        .next_index(index)
+                        .is_some_and(|index| self.tokens[index] == Token::LeftParenthesis);
+                    let previous = self.previous_index(index).map(|index| self.tokens[index]);
+
+                    if !is_call {
+                        continue;
+                    }
+
+                    if self.lint_config.banned.iter().any(|banned| banned == name)
+                        && !matches!(previous, Some(Token::Period | Token::Arrow))
+                    {
+                        self.report(Rule::BannedFunctions, index, format!("`{name}` is banned"));
+                    }
+
+                    let is_file_level = braces.iter().all(|(is_namespace, _)| *is_namespace);
+                    let is_declaration = parenthesis_depth == 0
+                        && previous.is_some_and(|previous| is_type_end(&previous));
+
+                    if is_file_level && is_declaration && name != "main" {
+                        self.check_name(index, names.functions, "function");
+
+                        if is_main_file
+                            && self.is_definition(index)
+                            && !statement.contains(&Token::Static)
+                        {
+                            self.report(
+                                Rule::MainHelpers,
+                                index,
+                                format!("helper function `{name}` of a main file should be static"),
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Check the name declared by the `struct`, `class`, `union` or `enum` at `index`.
+    fn check_type(&mut self, index: usize, case: Option<Case>) {
+        let mut name_index = self.next_index(index);
+
+        // `enum class name`
+        if name_index
+            .is_some_and(|index| matches!(self.tokens[index], Token::Class | Token::Struct))
+        {
+            name_index = name_index.and_then(|index| self.next_index(index));
+        }
+
+        let Some(name_index) = name_index else {
+            return;
+        };
+        // Only definitions and forward declarations, not the uses like `struct stat *s`
+        let is_declaration = self.next_index(name_index).is_some_and(|index| {
+            matches!(
+                self.tokens[index],
+                Token::LeftBrace | Token::SemiColon | Token::Colon
+            )
+        });
+
+        if is_declaration {
+            self.check_name(name_index, case, "type");
+        }
+    }
+
+    fn check_name(&mut self, index: usize, case: Option<Case>, kind: &str) {
+        if let (Token::Identifier(name), Some(case)) = (self.tokens[index], case) {
+            if !case.matches(name) {
+                self.report(
+                    Rule::Naming,
+                    index,
+                    format!("{kind} `{name}` should be in {case}"),
+                );
+            }
+        }
+    }
+
+    /// Whether the parameters after the function name at `index` are followed by a body.
+    fn is_definition(&self, index: usize) -> bool {
+        let mut depth = 0usize;
+
+        for (index, token) in self.tokens.iter().enumerate().skip(index + 1) {
+            match token {
+                Token::LeftParenthesis => depth += 1,
+                Token::RightParenthesis => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return (index + 1..self.tokens.len())
+                            .map(|index| self.tokens[index])
+                            .find(|token| {
+                                !is_blank(token)
+                                    && !matches!(
+                                        token,
+                                        Token::Const | Token::Noexcept | Token::Identifier(_)
+                                    )
+                            })
+                            == Some(Token::LeftBrace);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    /// `TODO`s must be followed by their owner in parentheses.
+    fn check_comments(&mut self) {
+        for index in 0..self.tokens.len() {
+            let (Token::SimpleComment(comment) | Token::MultilineComment(comment)) =
+                self.tokens[index]
+            else {
+                continue;
+            };
+
+            for (offset, _) in comment.match_indices("TODO") {
+                if comment[offset + 4..].starts_with('(') {
+                    continue;
+                }
+
+                let severity = Rule::TodoOwner.severity(self.lint_config);
+                let span = self.spans[index];
+                let before = &comment[..offset];
+                let (line, column) = match before.rfind('\n') {
+                    Some(newline) => (
+                        span.line + before.matches('\n').count(),
+                        before[newline + 1..].chars().count() + 1,
+                    ),
+                    // After the `//` or `/*`
+                    None => (span.line, span.column + 2 + before.chars().count()),
+                };
+
+                if severity != Severity::Off {
+                    self.diagnostics.push(Diagnostic {
+                        rule: Rule::TodoOwner,
+                        severity,
+                        line,
+                        column,
+                        message: "`TODO` without an owner, like `TODO(name)`".to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Token indices of the directives of an include guard and of their macro names.
+#[derive(Debug, Clone, Copy)]
+pub struct IncludeGuard {
+    pub ifndef: usize,
+    pub ifndef_name: usize,
+    pub define_name: usize,
+    pub endif: usize,
+}
+
+/// The include guard of a header, starting it with `#ifndef` and a `#define` without value of the
+/// same macro and ending it with the matching `#endif`, followed only by comments.
+pub fn find_include_guard(tokens: &[Token]) -> Option<IncludeGuard> {
+    let code: Vec<usize> = (0..tokens.len())
+        .filter(|index| !is_blank(&tokens[*index]) && tokens[*index] != Token::EndOfFile)
+        .collect();
+    let directive_name = |index: usize| match tokens[index] {
+        Token::Macro(name) => name.trim_start(),
+        _ => "",
+    };
+    let ifndef = *code.first()?;
+    let ifndef_name = *code.get(1)?;
+    let define = *code.get(2)?;
+    let define_name = *code.get(3)?;
+
+    if directive_name(ifndef) != "ifndef"
+        || !matches!(tokens[ifndef_name], Token::Identifier(_))
+        || directive_name(define) != "define"
+        || tokens[define_name] != tokens[ifndef_name]
+    {
+        return None;
+    }
+
+    // `#define BUFSIZE 64` is a constant, not a guard
+    if tokens[define_name + 1..]
+        .iter()
+        .take_while(|token| **token != Token::Newline)
+        .any(|token| !is_blank(token) && *token != Token::EndOfFile)
+    {
+        return None;
+    }
+
+    let mut depth = 0;
+    let endif = code.iter().copied().find(|index| {
+        match directive_name(*index) {
+            "if" | "ifdef" | "ifndef" => depth += 1,
+            "endif" => depth -= 1,
+            _ => {}
+        }
+
+        depth == 0
+    })?;
+
+    // Only comments may follow the line of the `#endif`
+    if tokens[endif..]
+        .iter()
+        .skip_while(|token| **token != Token::Newline)
+        .any(|token| !is_blank(token) && *token != Token::EndOfFile)
+    {
+        return None;
+    }
+
+    Some(IncludeGuard {
+        ifndef,
+        ifndef_name,
+        define_name,
+        endif,
+    })
+}
+
+/// Token index of the `#pragma once` starting a header.
+pub fn find_pragma_once(tokens: &[Token]) -> Option<usize> {
+    let mut code = (0..tokens.len()).filter(|index| !is_blank(&tokens[*index]));
+    let pragma = code.next()?;
+
+    (matches!(tokens[pragma], Token::Macro(name) if name.trim_start() == "pragma")
+        && code.next().map(|index| tokens[index]) == Some(Token::Identifier("once")))
+    .then_some(pragma)
+}
+
+/// Whether `token` can end the return type of a function declaration.
+fn is_type_end(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Void
+            | Token::Char
+            | Token::Short
+            | Token::Int
+            | Token::Long
+            | Token::Float
+            | Token::Double
+            | Token::Signed
+            | Token::Unsigned
+            | Token::Auto
+            | Token::Const
+            | Token::Times
+            | Token::Ampersand
+            | Token::RightAngle
+    )
+}
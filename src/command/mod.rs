@@ -1,21 +1,66 @@
 mod build;
 mod clean;
+mod config;
+mod doc;
+mod fix;
 mod format;
 mod init;
+mod lint;
 mod run;
 
 use std::path::{Path, PathBuf};
 
+use blake3::hash;
+
+use crate::config::platform::Platform;
+
 pub use build::*;
 pub use clean::*;
+pub use config::*;
+pub use doc::*;
+pub use fix::*;
 pub use format::*;
 pub use init::*;
+pub use lint::*;
 pub use run::*;
 
 pub fn add_mode_path(path: &Path, release: bool) -> PathBuf {
     path.join(if release { "release" } else { "debug" })
 }
 
+/// Name of the objects folder and hash file of a build, `debug` or `release` after the target when
+/// it isn't the host and followed by a hash of the user features when some are enabled, so that
+/// each target and feature set keeps its own objects.
+pub fn get_cache_name(platform: &Platform) -> String {
+    let mode = if platform.profile == "release" {
+        "release"
+    } else {
+        "debug"
+    };
+    let mode = if platform.cross {
+        format!("{}-{mode}", platform.target)
+    } else {
+        mode.to_string()
+    };
+
+    if platform.user_features.is_empty() {
+        return mode;
+    }
+
+    let mut features: Vec<&str> = platform
+        .user_features
+        .iter()
+        .map(|feature| feature.as_str())
+        .collect();
+
+    features.sort();
+
+    format!(
+        "{mode}-{}",
+        &hash(features.join(",").as_bytes()).to_hex()[..16]
+    )
+}
+
 pub fn get_project_path(config_file: &str) -> (PathBuf, PathBuf) {
     let project_config_path = Path::new(config_file);
 
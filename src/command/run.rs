@@ -1,5 +1,4 @@
 use std::{
-    env,
     io::{stderr, stdout},
     path::PathBuf,
     process::{Command, Stdio},
@@ -27,6 +26,7 @@ pub fn run(
             release,
             rebuild,
             pretty: true,
+            ..Default::default()
         },
         &mut stderr(),
     )?;
@@ -40,7 +40,12 @@ pub fn run(
             };
             let mut output_file = add_mode_path(&package_config.binaries, release).join(file);
 
-            output_file.set_extension(env::consts::EXE_EXTENSION);
+            output_file.set_extension(
+                project_config
+                    .toolchain
+                    .executable_extension
+                    .unwrap_or_default(),
+            );
 
             let output_file_exist = project_path.join(&output_file).exists();
 
@@ -78,7 +83,7 @@ pub fn run(
                     .unwrap();
             }
         }
-        Err(error) => ProjectConfig::handle_error(error, project_config_path)?,
+        Err(error) => return Err(error.into()),
     }
 
     Ok(())
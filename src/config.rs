@@ -1,40 +1,59 @@
 use std::{
     collections, env,
     fs::read_to_string,
+    hash::Hash,
     io::{self, stderr},
     path::{Path, PathBuf},
-    slice::IterMut,
 };
 
+use cfg::Cfg;
 use crossterm::{
     execute,
     style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
 };
 use dependency::DependencyConfig;
-use features::get_features;
-use hashbrown::HashMap;
+use error::{closest, ConfigError};
+use format::FormatConfig;
+use hashbrown::{hash_map::Entry, HashMap, HashSet};
+use interpolate::{interpolate, InterpolationError};
 use lib::LibConfig;
+use lint::LintConfig;
 use package::PackageConfig;
-use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
+use platform::Platform;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::{formats::PreferOne, serde_as, OneOrMany};
 use specific::SpecificConfig;
 use string_template::Template;
+use toolchain::ToolchainConfig;
 
 use crate::{
     file::{get_language, Language},
     pkg_config::ParsePkgVersion,
 };
 
+pub mod cfg;
 pub mod dependency;
+pub mod error;
 pub mod features;
+pub mod format;
 pub mod hash;
+pub mod interpolate;
 pub mod lib;
+pub mod lint;
 pub mod package;
+pub mod platform;
 pub mod specific;
+pub mod toolchain;
 
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct ProjectConfig {
+    /// Configs this one builds on, merged in order before it
+    #[serde(default, alias = "include")]
+    #[serde_as(deserialize_as = "OneOrMany<_, PreferOne>")]
+    pub extends: Vec<PathBuf>,
+
     pub package: Option<PackageConfig>,
 
     #[serde(default = "ProjectConfig::default_dependencies")]
@@ -56,12 +75,54 @@ pub struct ProjectConfig {
     #[serde(default = "ProjectConfig::default_hashmap")]
     #[serde(alias = "os", rename = "os-specific")]
     pub os_specific: HashMap<String, SpecificConfig>,
+
+    /// Specific configs selected by a `cfg(...)` expression
+    #[serde(default = "ProjectConfig::default_hashmap")]
+    pub target: HashMap<Cfg, SpecificConfig>,
+
+    /// Features enabling other features or optional dependencies with `dep:name`, the `default`
+    /// ones being enabled unless asked otherwise
+    #[serde(default = "ProjectConfig::default_hashmap")]
+    pub features: HashMap<String, Vec<String>>,
+
+    /// Toolchains by target triple, like `aarch64-linux-gnu`
+    #[serde(default = "ProjectConfig::default_hashmap")]
+    #[serde(rename = "toolchain")]
+    pub toolchains: HashMap<String, ToolchainConfig>,
+
+    #[serde(default, alias = "fmt")]
+    pub format: FormatConfig,
+
+    #[serde(default)]
+    pub lint: LintConfig,
+
+    /// Sections the merged values come from, like `package` or `os-specific.linux`, by key like
+    /// `package.c-compiler`, with one section per item for lists
+    #[serde(skip)]
+    pub origins: HashMap<String, Vec<String>>,
+
+    /// Toolchain of the platform the config is loaded for, complete but for the sysroot and the
+    /// compilers of the host
+    #[serde(skip)]
+    pub toolchain: ToolchainConfig,
+
+    /// Files of the configs extended directly or not
+    #[serde(skip)]
+    pub extended_paths: Vec<PathBuf>,
+
+    /// User-wide defaults, merged before the specific configs
+    #[serde(skip)]
+    user_config: Option<SpecificConfig>,
+
+    /// Machine-specific settings of the project, merged after the specific configs
+    #[serde(skip)]
+    local_config: Option<SpecificConfig>,
 }
 
 impl ProjectConfig {
     pub fn get_compiler(&self, file: &Path) -> Option<String> {
         file.extension()
-            .map(|extention| match get_language(extention) {
+            .and_then(|extention| match get_language(extention) {
                 Language::C => self
                     .package
                     .as_ref()
@@ -72,43 +133,129 @@ impl ProjectConfig {
                     .map(|package| package.cpp_compiler.clone()),
                 Language::Other => None,
             })
-            .flatten()
     }
 
-    pub fn handle_error(error: io::Error, project_config_path: &Path) -> io::Result<()> {
-        if let io::ErrorKind::Other = error.kind() {
-            execute!(
-                stderr(),
-                SetForegroundColor(Color::DarkRed),
-                Print("Error ".bold()),
-                ResetColor,
-                Print(project_config_path.to_string_lossy().bold()),
-                Print(" :\n\n".bold()),
-                Print(error.to_string()),
-            )
-        } else {
-            execute!(
-                stderr(),
-                SetForegroundColor(Color::DarkRed),
-                Print("Project config not found !\n".bold()),
-                ResetColor,
-            )
-        }
+    pub fn handle_error(error: ConfigError) -> io::Result<()> {
+        execute!(
+            stderr(),
+            SetForegroundColor(Color::DarkRed),
+            Print("Error ".bold()),
+            ResetColor,
+            Print(format!("{error}\n")),
+        )
     }
 
     fn default_dependencies() -> HashMap<String, DependencyConfig> {
         HashMap::new()
     }
 
-    fn default_hashmap<T>() -> HashMap<String, T> {
+    fn default_hashmap<K, T>() -> HashMap<K, T> {
         HashMap::new()
     }
 
-    fn merge_specific_config(&mut self) {
-        let package = self
-            .package
-            .as_mut()
-            .expect("Expected a package section in Maky.toml");
+    /// Enable the features asked by `platform`, the default ones and the ones they imply, then drop
+    /// the optional dependencies no feature enables.
+    fn resolve_features(
+        &mut self,
+        file_path: &Path,
+        platform: &mut Platform,
+    ) -> Result<(), ConfigError> {
+        let mut features = HashSet::new();
+        let mut dependencies = HashSet::new();
+        // Features with whether a `[features]` entry names them, rather than `--features`
+        let mut stack: Vec<(String, bool)> = platform
+            .user_features
+            .drain()
+            .map(|feature| (feature, false))
+            .collect();
+        let mut paths = vec![file_path.to_path_buf()];
+
+        paths.extend(self.extended_paths.iter().cloned());
+
+        let error = |message: String, suggestion: Option<String>, feature: &str, in_config| {
+            if in_config {
+                ConfigError::at_text(
+                    message,
+                    suggestion,
+                    Some("features"),
+                    &format!("\"{feature}\""),
+                    &paths,
+                )
+            } else {
+                ConfigError::invalid(message + " given to `--features`", suggestion, file_path)
+            }
+        };
+
+        if platform.default_features {
+            stack.extend(
+                self.features
+                    .get("default")
+                    .into_iter()
+                    .flatten()
+                    .map(|feature| (feature.clone(), true)),
+            );
+        }
+
+        while let Some((feature, in_config)) = stack.pop() {
+            if let Some(dependency) = feature.strip_prefix("dep:") {
+                if !self
+                    .dependencies
+                    .get(dependency)
+                    .is_some_and(|dependency| dependency.is_optional())
+                {
+                    return Err(error(
+                        format!("`{feature}` doesn't name an optional dependency"),
+                        closest(
+                            dependency,
+                            self.dependencies
+                                .iter()
+                                .filter(|(_, dependency)| dependency.is_optional())
+                                .map(|(name, _)| name.as_str()),
+                        ),
+                        &feature,
+                        in_config,
+                    ));
+                }
+
+                dependencies.insert(dependency.to_string());
+            } else if let Some(implied_features) = self.features.get(&feature) {
+                if features.insert(feature.clone()) {
+                    stack.extend(
+                        implied_features
+                            .iter()
+                            .map(|feature| (feature.clone(), true)),
+                    );
+                }
+            } else if self
+                .dependencies
+                .get(&feature)
+                .is_some_and(|dependency| dependency.is_optional())
+            {
+                // An optional dependency is also a feature of the same name
+                dependencies.insert(feature.clone());
+                features.insert(feature);
+            } else {
+                return Err(error(
+                    format!("unknown feature `{feature}`"),
+                    closest(&feature, self.features.keys().map(|name| name.as_str())),
+                    &feature,
+                    in_config,
+                ));
+            }
+        }
+
+        features.remove("default");
+        self.dependencies
+            .retain(|name, dependency| !dependency.is_optional() || dependencies.contains(name));
+        platform.user_features = features;
+
+        Ok(())
+    }
+
+    fn merge_specific_config(&mut self, platform: &Platform) {
+        let Some(package) = self.package.as_mut() else {
+            return;
+        };
         let mut specific_config = SpecificConfig {
             c_compiler: None,
             cpp_compiler: None,
@@ -118,14 +265,44 @@ impl ProjectConfig {
             includes: None,
             libraries: None,
         };
-        let features = get_features();
-        let mut oss = vec![env::consts::OS];
+        let mut specific_origins: HashMap<String, Vec<String>> = HashMap::new();
+        let mut oss = vec![platform.os.as_str()];
 
-        if env::consts::OS != env::consts::FAMILY {
-            oss.push(env::consts::FAMILY);
+        if platform.os != platform.family {
+            oss.push(platform.family.as_str());
         }
 
-        let mut inner_merge_specific_config = |selected_specific_config: Option<
+        // The user config holds defaults, so it only sets what the package leaves to Maky and adds
+        // the libraries the project doesn't have
+        let user_config = self.user_config.as_ref().map(|user_config| SpecificConfig {
+            c_compiler: user_config
+                .c_compiler
+                .clone()
+                .filter(|_| package.c_compiler == PackageConfig::default_c_compiler()),
+            cpp_compiler: user_config
+                .cpp_compiler
+                .clone()
+                .filter(|_| package.cpp_compiler == PackageConfig::default_cpp_compiler()),
+            binaries: user_config
+                .binaries
+                .clone()
+                .filter(|_| package.binaries == PackageConfig::default_binaries()),
+            objects: user_config
+                .objects
+                .clone()
+                .filter(|_| package.objects == PackageConfig::default_objects()),
+            sources: user_config.sources.clone(),
+            includes: user_config.includes.clone(),
+            libraries: user_config.libraries.as_ref().map(|libraries| {
+                libraries
+                    .iter()
+                    .filter(|(name, _)| !self.libraries.contains_key(*name))
+                    .map(|(name, library)| (name.clone(), library.clone()))
+                    .collect()
+            }),
+        });
+        let mut inner_merge_specific_config = |origin: String,
+                                               selected_specific_config: Option<
             &SpecificConfig,
         >| {
             if let Some(selected_specific_config) = selected_specific_config {
@@ -135,6 +312,8 @@ impl ProjectConfig {
                     } else {
                         specific_config.c_compiler = Some(specific_c_compiler);
                     }
+
+                    specific_origins.insert("c-compiler".to_string(), vec![origin.clone()]);
                 }
 
                 if let Some(specific_cpp_compiler) = selected_specific_config.cpp_compiler.clone() {
@@ -143,6 +322,8 @@ impl ProjectConfig {
                     } else {
                         specific_config.cpp_compiler = Some(specific_cpp_compiler);
                     }
+
+                    specific_origins.insert("cpp-compiler".to_string(), vec![origin.clone()]);
                 }
 
                 if let Some(specific_binaries) = selected_specific_config.binaries.clone() {
@@ -151,6 +332,8 @@ impl ProjectConfig {
                     } else {
                         specific_config.binaries = Some(specific_binaries);
                     }
+
+                    specific_origins.insert("binaries".to_string(), vec![origin.clone()]);
                 }
 
                 if let Some(specific_objects) = selected_specific_config.objects.clone() {
@@ -159,9 +342,16 @@ impl ProjectConfig {
                     } else {
                         specific_config.objects = Some(specific_objects);
                     }
+
+                    specific_origins.insert("objects".to_string(), vec![origin.clone()]);
                 }
 
                 if let Some(specific_sources) = selected_specific_config.sources.clone() {
+                    specific_origins
+                        .entry("sources".to_string())
+                        .or_default()
+                        .extend(vec![origin.clone(); specific_sources.len()]);
+
                     if let Some(sources) = &mut specific_config.sources {
                         sources.extend(specific_sources);
                     } else {
@@ -170,6 +360,11 @@ impl ProjectConfig {
                 }
 
                 if let Some(specific_includes) = selected_specific_config.includes.clone() {
+                    specific_origins
+                        .entry("includes".to_string())
+                        .or_default()
+                        .extend(vec![origin.clone(); specific_includes.len()]);
+
                     if let Some(includes) = &mut specific_config.includes {
                         includes.extend(specific_includes);
                     } else {
@@ -178,6 +373,11 @@ impl ProjectConfig {
                 }
 
                 if let Some(specific_libraries) = selected_specific_config.libraries.clone() {
+                    for library_name in specific_libraries.keys() {
+                        specific_origins
+                            .insert(format!("libraries.{library_name}"), vec![origin.clone()]);
+                    }
+
                     if let Some(libraries) = &mut specific_config.libraries {
                         libraries.extend(specific_libraries);
                     } else {
@@ -187,17 +387,110 @@ impl ProjectConfig {
             }
         };
 
-        inner_merge_specific_config(self.arch_specific.get(env::consts::ARCH));
+        inner_merge_specific_config("user-config".to_string(), user_config.as_ref());
+        inner_merge_specific_config(
+            format!("arch-specific.{}", platform.arch),
+            self.arch_specific.get(&platform.arch),
+        );
 
         for feature in self.feature_specific.keys() {
-            if features.contains(feature.as_str()) {
-                inner_merge_specific_config(self.feature_specific.get(feature));
+            if platform.features.contains(feature.as_str()) {
+                inner_merge_specific_config(
+                    format!("feature-specific.{feature}"),
+                    self.feature_specific.get(feature),
+                );
             }
         }
 
         for os in self.os_specific.keys() {
             if oss.contains(&os.as_str()) {
-                inner_merge_specific_config(self.os_specific.get(os));
+                inner_merge_specific_config(format!("os-specific.{os}"), self.os_specific.get(os));
+            }
+        }
+
+        let mut cfgs: Vec<&Cfg> = self
+            .target
+            .keys()
+            .filter(|cfg| cfg.matches(platform))
+            .collect();
+
+        cfgs.sort_by_key(|cfg| cfg.to_string());
+
+        for cfg in cfgs {
+            inner_merge_specific_config(format!("target.'cfg({cfg})'"), self.target.get(cfg));
+        }
+
+        inner_merge_specific_config("local-config".to_string(), self.local_config.as_ref());
+
+        let origins = &mut self.origins;
+        let package_origin = |is_default: bool| {
+            if is_default {
+                "default".to_string()
+            } else {
+                "package".to_string()
+            }
+        };
+
+        origins.insert(
+            "package.c-compiler".to_string(),
+            vec![package_origin(
+                package.c_compiler == PackageConfig::default_c_compiler(),
+            )],
+        );
+        origins.insert(
+            "package.cpp-compiler".to_string(),
+            vec![package_origin(
+                package.cpp_compiler == PackageConfig::default_cpp_compiler(),
+            )],
+        );
+        origins.insert(
+            "package.binaries".to_string(),
+            vec![package_origin(
+                package.binaries == PackageConfig::default_binaries(),
+            )],
+        );
+        origins.insert(
+            "package.objects".to_string(),
+            vec![package_origin(
+                package.objects == PackageConfig::default_objects(),
+            )],
+        );
+        origins.insert(
+            "package.sources".to_string(),
+            vec![
+                package_origin(package.sources == PackageConfig::default_sources());
+                package.sources.len()
+            ],
+        );
+        origins.insert(
+            "package.includes".to_string(),
+            vec![
+                package_origin(package.includes == PackageConfig::default_includes());
+                package.includes.len()
+            ],
+        );
+
+        for library_name in self.libraries.keys() {
+            origins.insert(
+                format!("libraries.{library_name}"),
+                vec!["libraries".to_string()],
+            );
+        }
+
+        for (key, specific_origin) in specific_origins.into_iter() {
+            match key.as_str() {
+                "sources" | "includes" => origins
+                    .entry(format!("package.{key}"))
+                    .or_default()
+                    .extend(specific_origin),
+                "c-compiler" | "cpp-compiler" | "binaries" | "objects" => {
+                    origins.insert(format!("package.{key}"), specific_origin);
+                }
+                // Libraries are keyed `libraries.<name>`, so a library named like a package key
+                // keeps its own origin
+                _ => {
+                    origins.insert(key, specific_origin);
+                }
             }
         }
 
@@ -209,6 +502,47 @@ impl ProjectConfig {
             package.cpp_compiler = specific_cpp_compiler;
         }
 
+        // The compilers of the toolchain build for the platform, so they come before the ones of
+        // the config
+        for (key, compiler, value) in [
+            (
+                "c-compiler",
+                &mut package.c_compiler,
+                &self.toolchain.c_compiler,
+            ),
+            (
+                "cpp-compiler",
+                &mut package.cpp_compiler,
+                &self.toolchain.cpp_compiler,
+            ),
+        ] {
+            if let Some(value) = value {
+                let toolchain_origin = origins
+                    .get(&format!("toolchain.{key}"))
+                    .cloned()
+                    .unwrap_or_default();
+
+                *compiler = value.clone();
+                origins.insert(format!("package.{key}"), toolchain_origin);
+            }
+        }
+
+        // Compilers set by distributions and CI come before the ones of the config
+        for (variable, key, compiler) in [
+            ("CC", "c-compiler", &mut package.c_compiler),
+            ("CXX", "cpp-compiler", &mut package.cpp_compiler),
+        ] {
+            let variable = platform.env_variable(variable);
+
+            if let Some(value) = env::var(&variable)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+            {
+                *compiler = value;
+                origins.insert(format!("package.{key}"), vec![format!("env.{variable}")]);
+            }
+        }
+
         if let Some(specific_binaries) = specific_config.binaries {
             package.binaries = specific_binaries;
         }
@@ -232,10 +566,11 @@ impl ProjectConfig {
             }
         }
 
-        'main: for library_config in self.libraries.values_mut() {
+        'main: for (library_name, library_config) in self.libraries.iter_mut() {
             let mut library = Vec::new();
             let mut directories = Vec::new();
             let mut includes = Vec::new();
+            let mut pkg_origin = String::new();
 
             for (pkg_name, pkg_version) in library_config.pkg_config.iter() {
                 if let Ok(pkg_config) = pkg_config::Config::new()
@@ -247,11 +582,33 @@ impl ProjectConfig {
                     library = pkg_config.libs;
                     directories = pkg_config.link_paths;
                     includes = pkg_config.include_paths;
+                    pkg_origin = format!("pkg-config.{pkg_name}");
                 } else {
                     continue 'main;
                 }
             }
 
+            let library_origin = origins
+                .get(&format!("libraries.{library_name}"))
+                .and_then(|library_origins| library_origins.first())
+                .cloned()
+                .unwrap_or("libraries".to_string());
+
+            for (key, pkg_count, count) in [
+                ("library", library.len(), library_config.library.len()),
+                (
+                    "directories",
+                    directories.len(),
+                    library_config.directories.len(),
+                ),
+                ("includes", includes.len(), library_config.includes.len()),
+            ] {
+                let mut key_origins = vec![pkg_origin.clone(); pkg_count];
+
+                key_origins.extend(vec![library_origin.clone(); count]);
+                origins.insert(format!("libraries.{library_name}.{key}"), key_origins);
+            }
+
             library.extend_from_slice(&library_config.library);
             directories.extend_from_slice(&library_config.directories);
             includes.extend_from_slice(&library_config.includes);
@@ -262,40 +619,633 @@ impl ProjectConfig {
         }
     }
 
-    pub fn load_without_processing(file_path: &Path) -> io::Result<Self> {
-        toml::from_str(&read_to_string(file_path)?)
-            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+    /// Use the compilers given on the command line, which come before the `CC` and `CXX`
+    /// environment variables, the specific configs and the package.
+    pub fn override_compilers(&mut self, c_compiler: Option<&str>, cpp_compiler: Option<&str>) {
+        let Some(package) = self.package.as_mut() else {
+            return;
+        };
+
+        for (option, key, compiler, value) in [
+            ("--cc", "c-compiler", &mut package.c_compiler, c_compiler),
+            (
+                "--cxx",
+                "cpp-compiler",
+                &mut package.cpp_compiler,
+                cpp_compiler,
+            ),
+        ] {
+            if let Some(value) = value {
+                *compiler = value.to_string();
+                self.origins
+                    .insert(format!("package.{key}"), vec![option.to_string()]);
+            }
+        }
     }
 
-    pub fn load(file_path: &Path) -> io::Result<Self> {
-        let mut project_config = ProjectConfig::load_without_processing(file_path)?;
+    /// Expand the `{{name}}` templates then the `${name}` variables of the string and path values,
+    /// the metadata of the package and the platform being the variables.
+    fn interpolate(
+        &mut self,
+        file_path: &Path,
+        platform: &Platform,
+    ) -> Result<(), InterpolationError> {
+        let package = self.package.as_mut().unwrap();
+        let metadata = package.get_metadata(file_path.parent().unwrap_or(Path::new("./")));
+        let mut template_values = collections::HashMap::from([
+            ("os", platform.os.as_str()),
+            ("family", platform.family.as_str()),
+            ("arch", platform.arch.as_str()),
+        ]);
+        let mut variables: HashMap<&str, String> = HashMap::from([
+            ("os", platform.os.clone()),
+            ("family", platform.family.clone()),
+            ("arch", platform.arch.clone()),
+            ("env", platform.env.clone()),
+            ("profile", platform.profile.clone()),
+            ("target", platform.target.clone()),
+        ]);
 
-        if project_config.package.is_some() {
-            project_config.merge_specific_config();
-
-            let package = project_config.package.as_mut().unwrap();
-            let template_values = collections::HashMap::from([
-                ("os", env::consts::OS),
-                ("family", env::consts::FAMILY),
-                ("arch", env::consts::ARCH),
-            ]);
-            let generate_path_variant = |paths: IterMut<PathBuf>| {
-                for path in paths {
-                    *path = PathBuf::from(
-                        Template::new(&path.to_string_lossy()).render(&template_values),
-                    );
+        template_values.extend(metadata.iter().map(|(key, value)| (*key, value.as_str())));
+        variables.extend(metadata.iter().cloned());
+
+        let expand = |value: &mut String| -> Result<(), InterpolationError> {
+            *value = interpolate(&Template::new(value).render(&template_values), &variables)?;
+
+            Ok(())
+        };
+        let expand_path = |path: &mut PathBuf| -> Result<(), InterpolationError> {
+            let mut value = path.to_string_lossy().to_string();
+
+            expand(&mut value)?;
+            *path = PathBuf::from(value);
+
+            Ok(())
+        };
+
+        expand(&mut package.c_compiler)?;
+        expand(&mut package.cpp_compiler)?;
+        package.standard.iter_mut().try_for_each(expand)?;
+        expand_path(&mut package.binaries)?;
+        expand_path(&mut package.objects)?;
+        package.sources.iter_mut().try_for_each(expand_path)?;
+        package.includes.iter_mut().try_for_each(expand_path)?;
+
+        self.toolchain.c_compiler.iter_mut().try_for_each(expand)?;
+        self.toolchain
+            .cpp_compiler
+            .iter_mut()
+            .try_for_each(expand)?;
+        self.toolchain
+            .sysroot
+            .iter_mut()
+            .try_for_each(expand_path)?;
+        self.toolchain.archiver.iter_mut().try_for_each(expand)?;
+
+        for library in self.libraries.values_mut() {
+            library.library.iter_mut().try_for_each(expand)?;
+            library.directories.iter_mut().try_for_each(expand_path)?;
+            library.includes.iter_mut().try_for_each(expand_path)?;
+        }
+
+        for dependency in self.dependencies.values_mut() {
+            match dependency {
+                DependencyConfig::Local { path, .. } => expand_path(path)?,
+                DependencyConfig::Git { git, rev, .. } => {
+                    expand(git)?;
+                    rev.iter_mut().try_for_each(expand)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load_without_processing(file_path: &Path) -> Result<Self, ConfigError> {
+        Ok(ProjectConfig::load_chain(file_path, &mut Vec::new())?.0)
+    }
+
+    /// Load a config merged over the configs it extends, with the sections it declares among
+    /// `format` and `lint`, and the `package.<field>` it sets.
+    ///
+    /// `chain` holds the configs being extended, to detect cycles.
+    fn load_chain(
+        file_path: &Path,
+        chain: &mut Vec<PathBuf>,
+    ) -> Result<(Self, HashSet<String>), ConfigError> {
+        let code =
+            read_to_string(file_path).map_err(|error| ConfigError::from_io(error, file_path))?;
+        let project_config: ProjectConfig = toml::from_str(&code)
+            .map_err(|error| ConfigError::from_toml(error, &code, file_path))?;
+        let table: toml::Table = toml::from_str(&code)
+            .map_err(|error| ConfigError::from_toml(error, &code, file_path))?;
+        let mut sections: HashSet<String> = [("format", "fmt"), ("lint", "lint")]
+            .into_iter()
+            .filter(|(name, alias)| table.contains_key(*name) || table.contains_key(*alias))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        let package_fields: Vec<&str> = match table.get("package") {
+            Some(toml::Value::Table(package)) => package
+                .keys()
+                .filter_map(|key| PackageConfig::field_name(key))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        // The project names and versions the package, the configs it extends only share settings
+        if chain.is_empty() {
+            if project_config.package.is_some() && !package_fields.contains(&"version") {
+                return Err(ConfigError::at_text(
+                    "missing field `version`".to_string(),
+                    None,
+                    None,
+                    "[package]",
+                    &[file_path.to_path_buf()],
+                ));
+            }
+        } else if let Some(field) = package_fields
+            .iter()
+            .find(|field| ["name", "version"].contains(field))
+        {
+            return Err(ConfigError::at_text(
+                format!("extended config can't set the package {field}"),
+                Some(format!("set the {field} in the [package] of the project")),
+                Some("package"),
+                field,
+                &[file_path.to_path_buf()],
+            ));
+        }
+
+        sections.extend(
+            package_fields
+                .into_iter()
+                .map(|field| format!("package.{field}")),
+        );
+
+        if project_config.extends.is_empty() {
+            return Ok((project_config, sections));
+        }
+
+        let config_path = file_path.parent().unwrap_or(Path::new("./"));
+        let mut extended_config: Option<ProjectConfig> = None;
+        let mut extended_sections = HashSet::new();
+        let mut extended_paths = Vec::new();
+
+        chain.push(file_path.canonicalize().unwrap_or(file_path.to_path_buf()));
+
+        for extends in project_config.extends.iter() {
+            let extends_str = extends.to_string_lossy().to_string();
+            let span = code
+                .find(&format!("\"{extends_str}\""))
+                .map(|start| start..start + extends_str.len() + 2);
+            let at_extends = |message: String| ConfigError::Invalid {
+                path: file_path.to_path_buf(),
+                message,
+                span: span.clone(),
+                code: code.clone(),
+                suggestion: None,
+            };
+            let extends_path = config_path.join(extends);
+            let Ok(canonical_extends_path) = extends_path.canonicalize() else {
+                return Err(at_extends(format!(
+                    "extended config `{extends_str}` not found"
+                )));
+            };
+
+            if let Some(index) = chain
+                .iter()
+                .position(|path| *path == canonical_extends_path)
+            {
+                let cycle = chain[index..]
+                    .iter()
+                    .chain([&canonical_extends_path])
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                return Err(at_extends(format!("extends cycle {cycle}")));
+            }
+
+            let (mut config, config_sections) = ProjectConfig::load_chain(&extends_path, chain)?;
+
+            config.rebase(extends.parent().unwrap_or(Path::new("")), &config_sections);
+            extended_paths.push(extends_path.clone());
+            extended_paths.append(&mut config.extended_paths);
+
+            extended_config = Some(match extended_config {
+                Some(mut extended_config) => {
+                    extended_config.merge(config, &config_sections);
+
+                    extended_config
                 }
+                None => config,
+            });
+            extended_sections.extend(config_sections);
+        }
+
+        chain.pop();
+
+        let mut extended_config = extended_config.unwrap();
+
+        // Without a package of its own, the project doesn't have one
+        if chain.is_empty() && project_config.package.is_none() {
+            extended_config.package = None;
+        }
+
+        extended_config.merge(project_config, &sections);
+        extended_config.extended_paths = extended_paths;
+        sections.extend(extended_sections);
+
+        Ok((extended_config, sections))
+    }
+
+    /// Merge `other` over this config, the libraries and specific configs with the same name
+    /// being merged and the entries of its other maps replacing them, and the package fields and
+    /// `sections` it declares replacing the ones of this config.
+    fn merge(&mut self, other: ProjectConfig, sections: &HashSet<String>) {
+        self.extends = other.extends;
+
+        if let Some(other_package) = other.package {
+            match &mut self.package {
+                Some(package) => package.merge(other_package, sections),
+                None => self.package = Some(other_package),
+            }
+        }
+
+        self.dependencies.extend(other.dependencies);
+        merge_entries(&mut self.libraries, other.libraries, LibConfig::merge);
+        merge_entries(
+            &mut self.arch_specific,
+            other.arch_specific,
+            SpecificConfig::merge,
+        );
+        merge_entries(
+            &mut self.feature_specific,
+            other.feature_specific,
+            SpecificConfig::merge,
+        );
+        merge_entries(
+            &mut self.os_specific,
+            other.os_specific,
+            SpecificConfig::merge,
+        );
+        merge_entries(&mut self.target, other.target, SpecificConfig::merge);
+        self.features.extend(other.features);
+        self.toolchains.extend(other.toolchains);
+
+        if sections.contains("format") {
+            self.format = other.format;
+        }
+
+        if sections.contains("lint") {
+            self.lint = other.lint;
+        }
+    }
+
+    /// Make the relative paths of a config extended from the `prefix` folder relative to the
+    /// extending config, except the ones starting with a variable and the package ones it doesn't
+    /// set among `sections`.
+    fn rebase(&mut self, prefix: &Path, sections: &HashSet<String>) {
+        let rebase_path = |path: &mut PathBuf| {
+            let path_str = path.to_string_lossy();
+
+            if path.is_relative() && !path_str.starts_with('$') && !path_str.starts_with("{{") {
+                *path = prefix.join(&path);
+            }
+        };
+        let rebase_library = |library: &mut LibConfig| {
+            library.directories.iter_mut().for_each(rebase_path);
+            library.includes.iter_mut().for_each(rebase_path);
+        };
+
+        for dependency in self.dependencies.values_mut() {
+            if let DependencyConfig::Local { path, .. } = dependency {
+                rebase_path(path);
+            }
+        }
+
+        if let Some(package) = &mut self.package {
+            if sections.contains("package.binaries") {
+                rebase_path(&mut package.binaries);
+            }
+            if sections.contains("package.objects") {
+                rebase_path(&mut package.objects);
+            }
+            if sections.contains("package.sources") {
+                package.sources.iter_mut().for_each(rebase_path);
+            }
+            if sections.contains("package.includes") {
+                package.includes.iter_mut().for_each(rebase_path);
+            }
+        }
+
+        self.libraries.values_mut().for_each(rebase_library);
+        self.toolchains
+            .values_mut()
+            .flat_map(|toolchain| toolchain.sysroot.as_mut())
+            .for_each(rebase_path);
+
+        for specific_config in self
+            .arch_specific
+            .values_mut()
+            .chain(self.feature_specific.values_mut())
+            .chain(self.os_specific.values_mut())
+            .chain(self.target.values_mut())
+        {
+            specific_config
+                .sources
+                .iter_mut()
+                .flatten()
+                .for_each(rebase_path);
+            specific_config
+                .includes
+                .iter_mut()
+                .flatten()
+                .for_each(rebase_path);
+            specific_config
+                .libraries
+                .iter_mut()
+                .flat_map(|libraries| libraries.values_mut())
+                .for_each(rebase_library);
+        }
+    }
+
+    pub fn load(file_path: &Path) -> Result<Self, ConfigError> {
+        ProjectConfig::load_for(file_path, &mut Platform::host())
+    }
+
+    /// Config next to the project config, like `Maky.local.toml` for `Maky.toml`, meant to stay
+    /// out of git.
+    pub fn get_local_config_path(file_path: &Path) -> PathBuf {
+        file_path.with_file_name(format!(
+            "{}.local.toml",
+            file_path.file_stem().unwrap_or_default().to_string_lossy()
+        ))
+    }
+
+    /// `maky/config.toml` of the config folder of the user, like `~/.config/maky/config.toml`.
+    pub fn get_user_config_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|config_path| config_path.join("maky/config.toml"))
+    }
+
+    /// Config of an optional file, like a specific config or a toolchain, `None` when the file
+    /// doesn't exist.
+    fn load_optional_config<T: DeserializeOwned>(
+        file_path: &Path,
+    ) -> Result<Option<T>, ConfigError> {
+        let code = match read_to_string(file_path) {
+            Ok(code) => code,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(ConfigError::from_io(error, file_path)),
+        };
+
+        toml::from_str(&code)
+            .map(Some)
+            .map_err(|error| ConfigError::from_toml(error, &code, file_path))
+    }
+
+    /// Toolchain of the platform, the `[toolchain.'<target>']` table or else the
+    /// `maky/toolchains/<target>.toml` file next to the user config, completed by the default
+    /// toolchain of the platform.
+    fn resolve_toolchain(&mut self, platform: &Platform) -> Result<(), ConfigError> {
+        let (toolchain, origin) = match self.toolchains.get(&platform.target) {
+            Some(toolchain) => (
+                toolchain.clone(),
+                format!("toolchain.'{}'", platform.target),
+            ),
+            None => match ProjectConfig::get_user_config_path().map(|user_config_path| {
+                user_config_path
+                    .with_file_name("toolchains")
+                    .join(format!("{}.toml", platform.target))
+            }) {
+                Some(toolchain_path) => (
+                    ProjectConfig::load_optional_config(&toolchain_path)?.unwrap_or_default(),
+                    toolchain_path.to_string_lossy().to_string(),
+                ),
+                None => (ToolchainConfig::default(), String::new()),
+            },
+        };
+
+        for key in ToolchainConfig::KEYS {
+            let key_origin = if toolchain.get(key).is_some() {
+                origin.clone()
+            } else {
+                "default".to_string()
             };
 
-            generate_path_variant(package.sources.iter_mut());
-            generate_path_variant(package.includes.iter_mut());
+            self.origins
+                .insert(format!("toolchain.{key}"), vec![key_origin]);
+        }
+
+        self.toolchain = toolchain.or(ToolchainConfig::default_for(platform));
+
+        Ok(())
+    }
+
+    /// Load the config with the user config, the specific configs of `platform` and the local
+    /// config merged in this order, once the user features and the toolchain of `platform` are
+    /// resolved.
+    ///
+    /// The binaries of a target other than the host go to a folder named after it.
+    pub fn load_for(file_path: &Path, platform: &mut Platform) -> Result<Self, ConfigError> {
+        let mut project_config = ProjectConfig::load_without_processing(file_path)?;
+
+        project_config.resolve_features(file_path, platform)?;
 
-            for library in project_config.libraries.values_mut() {
-                generate_path_variant(library.directories.iter_mut());
-                generate_path_variant(library.includes.iter_mut());
+        if project_config.package.is_some() {
+            if let Some(user_config_path) = ProjectConfig::get_user_config_path() {
+                project_config.user_config =
+                    ProjectConfig::load_optional_config(&user_config_path)?;
+            }
+
+            project_config.local_config = ProjectConfig::load_optional_config(
+                &ProjectConfig::get_local_config_path(file_path),
+            )?;
+            project_config.resolve_toolchain(platform)?;
+            project_config.merge_specific_config(platform);
+            project_config
+                .interpolate(file_path, platform)
+                .map_err(|error| {
+                    // The local config wins over the project config, which wins over the configs
+                    // it extends and the user config
+                    let mut paths = vec![
+                        ProjectConfig::get_local_config_path(file_path),
+                        file_path.to_path_buf(),
+                    ];
+
+                    paths.extend(project_config.extended_paths.iter().cloned());
+                    paths.extend(ProjectConfig::get_user_config_path());
+
+                    ConfigError::from_interpolation(error, &paths)
+                })?;
+
+            if platform.cross {
+                let package = project_config.package.as_mut().unwrap();
+
+                package.binaries = package.binaries.join(&platform.target);
             }
         }
 
         Ok(project_config)
     }
 }
+
+/// Merge the `other` entries into `entries` with `merge` when they share a key, inserting them
+/// otherwise.
+fn merge_entries<K: Eq + Hash, V>(
+    entries: &mut HashMap<K, V>,
+    other: HashMap<K, V>,
+    merge: fn(&mut V, V),
+) {
+    for (key, value) in other {
+        match entries.entry(key) {
+            Entry::Occupied(mut entry) => merge(entry.get_mut(), value),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    /// Folder of a test holding `files` by path, emptied first.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("maky-{name}-{}", std::process::id()));
+
+        remove_dir_all(&root).ok();
+
+        for (path, code) in files {
+            let path = root.join(path);
+
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, code).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn rebases_extended_paths() {
+        let root = write_files(
+            "rebase",
+            &[
+                (
+                    "common/base.toml",
+                    "[package]\nsources = \"shared\"\n\n[os-specific.linux]\nincludes = \"linux\"\n",
+                ),
+                (
+                    "app/Maky.toml",
+                    "extends = \"../common/base.toml\"\n\n[package]\nversion = \"0.1.0\"\n",
+                ),
+            ],
+        );
+        let project_config =
+            ProjectConfig::load_without_processing(&root.join("app/Maky.toml")).unwrap();
+        let package = project_config.package.unwrap();
+
+        assert_eq!(package.sources, [Path::new("../common/shared")]);
+        assert_eq!(package.binaries, PackageConfig::default_binaries());
+        assert_eq!(
+            project_config.os_specific["linux"].includes,
+            Some(vec![PathBuf::from("../common/linux")])
+        );
+
+        remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn merges_extended_libraries() {
+        let root = write_files(
+            "libraries",
+            &[
+                (
+                    "common/base.toml",
+                    "[libraries.m]\nlibrary = \"m\"\nincludes = \"include\"\n",
+                ),
+                (
+                    "app/Maky.toml",
+                    "extends = \"../common/base.toml\"\n\n[package]\nversion = \"0.1.0\"\n\n[libraries.m]\nlibrary = \"mvec\"\ndirectories = \"lib\"\n",
+                ),
+            ],
+        );
+        let project_config =
+            ProjectConfig::load_without_processing(&root.join("app/Maky.toml")).unwrap();
+        let library = &project_config.libraries["m"];
+
+        assert_eq!(library.library, ["m", "mvec"]);
+        assert_eq!(library.directories, [Path::new("lib")]);
+        assert_eq!(library.includes, [Path::new("../common/include")]);
+
+        remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn rejects_extends_cycle() {
+        let root = write_files(
+            "cycle",
+            &[
+                ("a.toml", "extends = \"b.toml\"\n"),
+                ("b.toml", "extends = \"a.toml\"\n"),
+            ],
+        );
+
+        match ProjectConfig::load_without_processing(&root.join("a.toml")) {
+            Err(ConfigError::Invalid { message, .. }) => {
+                assert!(message.starts_with("extends cycle"), "{message}")
+            }
+            result => panic!("expected an extends cycle, got {result:?}"),
+        }
+
+        remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn rejects_extended_package_version() {
+        let root = write_files(
+            "version",
+            &[
+                ("base.toml", "[package]\nversion = \"1.0.0\"\n"),
+                (
+                    "Maky.toml",
+                    "extends = \"base.toml\"\n\n[package]\nversion = \"0.1.0\"\n",
+                ),
+            ],
+        );
+
+        assert!(ProjectConfig::load_without_processing(&root.join("Maky.toml")).is_err());
+
+        remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn fills_package_defaults_with_user_config() {
+        let user_config = "c-compiler = \"clang\"\ncpp-compiler = \"clang++\"\n";
+        let mut project_config: ProjectConfig =
+            toml::from_str("[package]\nversion = \"0.1.0\"\nc-compiler = \"tcc\"\n").unwrap();
+
+        // The compilers of the environment come before both
+        env::remove_var("CC");
+        env::remove_var("CXX");
+        project_config.user_config = Some(toml::from_str(user_config).unwrap());
+        project_config.merge_specific_config(&Platform::host());
+
+        let package = project_config.package.unwrap();
+
+        assert_eq!(package.c_compiler, "tcc");
+        assert_eq!(package.cpp_compiler, "clang++");
+        assert_eq!(project_config.origins["package.c-compiler"], ["package"]);
+        assert_eq!(
+            project_config.origins["package.cpp-compiler"],
+            ["user-config"]
+        );
+    }
+}
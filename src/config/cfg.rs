@@ -0,0 +1,268 @@
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::platform::Platform;
+
+/// Names a `cfg(...)` predicate can test, bare or as `name = "value"`.
+const NAMES: [&str; 9] = [
+    "unix",
+    "windows",
+    "target_os",
+    "target_family",
+    "target_arch",
+    "target_env",
+    "target_feature",
+    "feature",
+    "profile",
+];
+
+/// Condition of a `[target.'cfg(...)']` table, like `cfg(all(unix, not(target_arch = "x86")))`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Cfg {
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+    /// Bare name, `unix` or `windows`
+    Name(String),
+    /// `name = "value"`
+    KeyValue(String, String),
+}
+
+impl Cfg {
+    /// Parse a full `cfg(...)` expression.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            expression,
+            chars: expression.char_indices().peekable(),
+        };
+        let name = parser.identifier()?;
+
+        if name != "cfg" {
+            return Err(format!(
+                "expected `cfg(...)`, found `{name}` in `{expression}`"
+            ));
+        }
+
+        parser.expect('(')?;
+        let cfg = parser.predicate()?;
+        parser.expect(')')?;
+        parser.skip_whitespaces();
+
+        if let Some((_, char)) = parser.chars.next() {
+            return Err(format!(
+                "unexpected `{char}` after `cfg(...)` in `{expression}`"
+            ));
+        }
+
+        Ok(cfg)
+    }
+
+    /// Whether the platform satisfies the condition.
+    pub fn matches(&self, platform: &Platform) -> bool {
+        match self {
+            Cfg::All(cfgs) => cfgs.iter().all(|cfg| cfg.matches(platform)),
+            Cfg::Any(cfgs) => cfgs.iter().any(|cfg| cfg.matches(platform)),
+            Cfg::Not(cfg) => !cfg.matches(platform),
+            Cfg::Name(name) => platform.family == *name,
+            Cfg::KeyValue(name, value) => match name.as_str() {
+                "target_os" => platform.os == *value,
+                "target_family" => platform.family == *value,
+                "target_arch" => platform.arch == *value,
+                "target_env" => platform.env == *value,
+                "target_feature" => platform.features.contains(value),
+                "feature" => {
+                    platform.features.contains(value) || platform.user_features.contains(value)
+                }
+                "profile" => platform.profile == *value,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, name: &str, cfgs: &[Cfg]| {
+            write!(f, "{name}(")?;
+
+            for (index, cfg) in cfgs.iter().enumerate() {
+                if index != 0 {
+                    write!(f, ", ")?;
+                }
+
+                write!(f, "{cfg}")?;
+            }
+
+            write!(f, ")")
+        };
+
+        match self {
+            Cfg::All(cfgs) => list(f, "all", cfgs),
+            Cfg::Any(cfgs) => list(f, "any", cfgs),
+            Cfg::Not(cfg) => write!(f, "not({cfg})"),
+            Cfg::Name(name) => write!(f, "{name}"),
+            Cfg::KeyValue(name, value) => write!(f, "{name} = \"{value}\""),
+        }
+    }
+}
+
+impl Serialize for Cfg {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format!("cfg({self})"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Cfg {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let expression = String::deserialize(deserializer)?;
+
+        Cfg::parse(&expression).map_err(de::Error::custom)
+    }
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespaces(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, char)| char.is_whitespace())
+            .is_some()
+        {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespaces();
+
+        match self.chars.next() {
+            Some((_, char)) if char == expected => Ok(()),
+            Some((_, char)) => Err(format!(
+                "expected `{expected}`, found `{char}` in `{}`",
+                self.expression
+            )),
+            None => Err(format!("expected `{expected}` in `{}`", self.expression)),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        self.skip_whitespaces();
+
+        let mut identifier = String::new();
+
+        while let Some((_, char)) = self
+            .chars
+            .next_if(|(_, char)| char.is_ascii_alphanumeric() || *char == '_')
+        {
+            identifier.push(char);
+        }
+
+        if identifier.is_empty() {
+            Err(format!("expected a name in `{}`", self.expression))
+        } else {
+            Ok(identifier)
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+
+        let mut string = String::new();
+
+        for (_, char) in self.chars.by_ref() {
+            if char == '"' {
+                return Ok(string);
+            }
+
+            string.push(char);
+        }
+
+        Err(format!("unterminated string in `{}`", self.expression))
+    }
+
+    /// Predicates separated by commas up to the closing parenthesis, which is left to the caller.
+    fn predicates(&mut self) -> Result<Vec<Cfg>, String> {
+        let mut cfgs = Vec::new();
+
+        loop {
+            self.skip_whitespaces();
+
+            if matches!(self.chars.peek(), Some((_, ')'))) {
+                return Ok(cfgs);
+            }
+
+            cfgs.push(self.predicate()?);
+            self.skip_whitespaces();
+
+            if self.chars.next_if(|(_, char)| *char == ',').is_none() {
+                return Ok(cfgs);
+            }
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Cfg, String> {
+        let name = self.identifier()?;
+
+        self.skip_whitespaces();
+
+        match self.chars.peek() {
+            Some((_, '(')) => {
+                self.chars.next();
+
+                let cfg = match name.as_str() {
+                    "all" => Cfg::All(self.predicates()?),
+                    "any" => Cfg::Any(self.predicates()?),
+                    "not" => Cfg::Not(Box::new(self.predicate()?)),
+                    _ => {
+                        return Err(format!(
+                            "unknown operator `{name}` in `{}`, expected `all`, `any` or `not`",
+                            self.expression
+                        ))
+                    }
+                };
+
+                self.expect(')')?;
+
+                Ok(cfg)
+            }
+            Some((_, '=')) => {
+                self.chars.next();
+
+                if !NAMES[2..].contains(&name.as_str()) {
+                    return Err(self.unknown_name(&name));
+                }
+
+                Ok(Cfg::KeyValue(name, self.string()?))
+            }
+            _ => {
+                if !NAMES[..2].contains(&name.as_str()) {
+                    return Err(self.unknown_name(&name));
+                }
+
+                Ok(Cfg::Name(name))
+            }
+        }
+    }
+
+    fn unknown_name(&self, name: &str) -> String {
+        let names = NAMES
+            .iter()
+            .map(|name| format!("`{name}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "unknown cfg name `{name}` in `{}`, expected one of {names}",
+            self.expression
+        )
+    }
+}
@@ -1,29 +1,94 @@
 use std::path::PathBuf;
 
 use semver::VersionReq;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Serialize, Deserialize, Debug)]
-#[serde(untagged)]
+#[serde(untagged, try_from = "RawDependencyConfig")]
 pub enum DependencyConfig {
     Local {
         #[serde(serialize_with = "DependencyConfig::serialize_version_req")]
-        #[serde(deserialize_with = "DependencyConfig::deserialize_version_req")]
-        #[serde(default)]
         version: Option<VersionReq>,
         path: PathBuf,
+        optional: bool,
     },
     Git {
         #[serde(serialize_with = "DependencyConfig::serialize_version_req")]
-        #[serde(deserialize_with = "DependencyConfig::deserialize_version_req")]
-        #[serde(default)]
         version: Option<VersionReq>,
         git: String,
         rev: Option<String>,
+        optional: bool,
     },
 }
 
+/// Dependency table before checking which kind of dependency it is, so that a typo in a key is
+/// reported as such.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDependencyConfig {
+    #[serde(deserialize_with = "DependencyConfig::deserialize_version_req")]
+    #[serde(default)]
+    version: Option<VersionReq>,
+    path: Option<PathBuf>,
+    git: Option<String>,
+    rev: Option<String>,
+    #[serde(default)]
+    optional: bool,
+}
+
+impl TryFrom<RawDependencyConfig> for DependencyConfig {
+    type Error = String;
+
+    fn try_from(raw: RawDependencyConfig) -> Result<Self, Self::Error> {
+        match raw {
+            RawDependencyConfig {
+                version,
+                path: Some(path),
+                git: None,
+                rev: None,
+                optional,
+            } => Ok(DependencyConfig::Local {
+                version,
+                path,
+                optional,
+            }),
+            RawDependencyConfig {
+                version,
+                path: None,
+                git: Some(git),
+                rev,
+                optional,
+            } => Ok(DependencyConfig::Git {
+                version,
+                git,
+                rev,
+                optional,
+            }),
+            RawDependencyConfig {
+                path: Some(_),
+                git: Some(_),
+                ..
+            } => Err("a dependency can't have both a `path` and a `git` url".to_string()),
+            RawDependencyConfig {
+                path: Some(_),
+                rev: Some(_),
+                ..
+            } => Err("`rev` is only for `git` dependencies".to_string()),
+            _ => Err("a dependency needs a `path` or a `git` url".to_string()),
+        }
+    }
+}
+
 impl DependencyConfig {
+    /// Whether the dependency is only used when a feature enables it.
+    pub fn is_optional(&self) -> bool {
+        match self {
+            DependencyConfig::Local { optional, .. } | DependencyConfig::Git { optional, .. } => {
+                *optional
+            }
+        }
+    }
+
     fn serialize_version_req<S>(
         version_req: &Option<VersionReq>,
         serializer: S,
@@ -44,8 +109,14 @@ impl DependencyConfig {
     {
         let version_req = Option::<String>::deserialize(deserializer)?;
 
-        Ok(version_req.map(|version_req| {
-            VersionReq::parse(&version_req).expect("Malformed version requirement in config")
-        }))
+        version_req
+            .map(|version_req| {
+                VersionReq::parse(&version_req).map_err(|error| {
+                    de::Error::custom(format!(
+                        "malformed version requirement `{version_req}`, {error}"
+                    ))
+                })
+            })
+            .transpose()
     }
 }
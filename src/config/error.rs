@@ -0,0 +1,217 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::read_to_string,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use strsim::damerau_levenshtein;
+
+use super::interpolate::InterpolationError;
+
+/// Problem with a Maky config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file doesn't exist
+    NotFound { path: PathBuf },
+    /// The config file exists but can't be read
+    Io { path: PathBuf, error: io::Error },
+    /// The config file isn't valid TOML or doesn't match what Maky expects
+    Invalid {
+        path: PathBuf,
+        message: String,
+        span: Option<Range<usize>>,
+        code: String,
+        suggestion: Option<String>,
+    },
+    /// The config has no `[package]` section where one is needed
+    MissingPackage { path: PathBuf },
+    /// A flag of the command line doesn't fit, like a malformed `--target-feature`
+    Flag(String),
+}
+
+impl ConfigError {
+    pub fn from_io(error: io::Error, path: &Path) -> Self {
+        if error.kind() == io::ErrorKind::NotFound {
+            ConfigError::NotFound {
+                path: path.to_path_buf(),
+            }
+        } else {
+            ConfigError::Io {
+                path: path.to_path_buf(),
+                error,
+            }
+        }
+    }
+
+    pub fn from_toml(error: toml::de::Error, code: &str, path: &Path) -> Self {
+        let message = error
+            .message()
+            .trim()
+            .lines()
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        ConfigError::Invalid {
+            path: path.to_path_buf(),
+            suggestion: suggest(&message),
+            message,
+            span: error.span(),
+            code: code.to_string(),
+        }
+    }
+
+    /// Variable that can't be expanded, pointing at its first use in the first of the merged
+    /// `paths` that has it, as values are expanded once merged.
+    pub fn from_interpolation(error: InterpolationError, paths: &[PathBuf]) -> Self {
+        ConfigError::at_text(
+            error.message,
+            error.suggestion,
+            None,
+            &error.expression,
+            paths,
+        )
+    }
+
+    /// Problem found once the configs are merged, pointing at the first `text` after the
+    /// `[section]` header, or anywhere without section, in the first of `paths` that has it.
+    pub fn at_text(
+        message: String,
+        suggestion: Option<String>,
+        section: Option<&str>,
+        text: &str,
+        paths: &[PathBuf],
+    ) -> Self {
+        let found = paths.iter().find_map(|path| {
+            let code = read_to_string(path).ok()?;
+            let section_start = match section {
+                Some(section) => code.find(&format!("[{section}]"))?,
+                None => 0,
+            };
+            let start = section_start + code[section_start..].find(text)?;
+
+            Some((path, start..start + text.len(), code))
+        });
+        let (path, span, code) = match found {
+            Some((path, span, code)) => (path.clone(), Some(span), code),
+            None => (
+                paths
+                    .iter()
+                    .find(|path| path.is_file())
+                    .unwrap_or(&paths[0])
+                    .clone(),
+                None,
+                String::new(),
+            ),
+        };
+
+        ConfigError::Invalid {
+            path,
+            message,
+            span,
+            code,
+            suggestion,
+        }
+    }
+
+    /// Problem found once the config is parsed, so without a span.
+    pub fn invalid(message: String, suggestion: Option<String>, path: &Path) -> Self {
+        ConfigError::Invalid {
+            path: path.to_path_buf(),
+            message,
+            span: None,
+            code: String::new(),
+            suggestion,
+        }
+    }
+}
+
+/// Closest name to `unknown` among `names`, as a suggestion when it looks like a typo.
+pub fn closest<'a>(unknown: &str, names: impl Iterator<Item = &'a str>) -> Option<String> {
+    names
+        .map(|name| (damerau_levenshtein(unknown, name), name))
+        .filter(|(distance, name)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| format!("did you mean `{name}` ?"))
+}
+
+/// Closest expected name of an `unknown field` or `unknown variant` message, when it looks like a typo.
+fn suggest(message: &str) -> Option<String> {
+    let rest = message
+        .strip_prefix("unknown field `")
+        .or_else(|| message.strip_prefix("unknown variant `"))?;
+    let (unknown, expected) = rest.split_once('`')?;
+
+    // The expected names are the odd parts between backquotes
+    closest(unknown, expected.split('`').skip(1).step_by(2))
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NotFound { path } => write!(
+                f,
+                "{} : project config not found\n  = help: run `maky init` to create a project",
+                path.to_string_lossy()
+            ),
+            ConfigError::Io { path, error } => write!(f, "{} : {error}", path.to_string_lossy()),
+            ConfigError::Flag(message) => write!(f, "{message}"),
+            ConfigError::MissingPackage { path } => write!(
+                f,
+                "{} : missing [package] section\n  = help: add a [package] section with a version",
+                path.to_string_lossy()
+            ),
+            ConfigError::Invalid {
+                path,
+                message,
+                span,
+                code,
+                suggestion,
+            } => {
+                let Some(span) = span else {
+                    write!(f, "{} : {message}", path.to_string_lossy())?;
+
+                    if let Some(suggestion) = suggestion {
+                        write!(f, "\n  = help: {suggestion}")?;
+                    }
+
+                    return Ok(());
+                };
+                let start = span.start.min(code.len());
+                let line_start = code[..start].rfind('\n').map_or(0, |index| index + 1);
+                let line = code[line_start..].lines().next().unwrap_or_default();
+                let line_number = (code[..start].matches('\n').count() + 1).to_string();
+                let column = code[line_start..start].chars().count() + 1;
+                let width = code[start..span.end.clamp(start, line_start + line.len())]
+                    .chars()
+                    .count()
+                    .max(1);
+                let padding = " ".repeat(line_number.len());
+
+                writeln!(
+                    f,
+                    "{}:{line_number}:{column} : {message}",
+                    path.to_string_lossy()
+                )?;
+                writeln!(f, "{padding} |")?;
+                writeln!(f, "{line_number} | {line}")?;
+                write!(
+                    f,
+                    "{padding} | {}{}",
+                    " ".repeat(column - 1),
+                    "^".repeat(width)
+                )?;
+
+                if let Some(suggestion) = suggestion {
+                    write!(f, "\n{padding} = help: {suggestion}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
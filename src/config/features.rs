@@ -1,5 +1,6 @@
 use hashbrown::HashSet;
 
+/// Features of the CPU Maky runs on, detected at runtime on x86 and aarch64.
 pub fn get_features() -> HashSet<&'static str> {
     let mut features = HashSet::new();
 
@@ -520,3 +521,48 @@ pub fn get_features() -> HashSet<&'static str> {
 
     features
 }
+
+/// Features every CPU of the architecture has, for a target other than the host.
+pub fn get_target_features(arch: &str) -> HashSet<&'static str> {
+    match arch {
+        "x86_64" => get_cpu_features("x86-64").unwrap_or_default(),
+        "x86" => HashSet::from(["fxsr", "mmx", "sse", "sse2"]),
+        "aarch64" => get_cpu_features("armv8-a").unwrap_or_default(),
+        _ => HashSet::new(),
+    }
+}
+
+/// Features of a `--target-cpu`, `None` when the CPU isn't one of the architecture levels Maky
+/// knows.
+pub fn get_cpu_features(cpu: &str) -> Option<HashSet<&'static str>> {
+    let levels: [&[&str]; 4] = match cpu {
+        "x86-64" | "x86-64-v2" | "x86-64-v3" | "x86-64-v4" => [
+            &["fxsr", "mmx", "sse", "sse2"],
+            &["cmpxchg16b", "popcnt", "sse3", "ssse3", "sse4.1", "sse4.2"],
+            &[
+                "avx", "avx2", "bmi1", "bmi2", "f16c", "fma", "lzcnt", "xsave",
+            ],
+            &["avx512f", "avx512bw", "avx512cd", "avx512dq", "avx512vl"],
+        ],
+        "armv8-a" | "armv8.1-a" | "armv8.2-a" => [
+            &["neon", "fp", "asimd"],
+            &["crc", "lse", "rdm", "v8.1a"],
+            &["ras", "dpb", "v8.2a"],
+            &[],
+        ],
+        _ => return None,
+    };
+    let level = match cpu {
+        "x86-64" | "armv8-a" => 1,
+        "x86-64-v2" | "armv8.1-a" => 2,
+        "x86-64-v3" | "armv8.2-a" => 3,
+        _ => 4,
+    };
+
+    Some(
+        levels[..level]
+            .iter()
+            .flat_map(|features| features.iter().copied())
+            .collect(),
+    )
+}
@@ -0,0 +1,286 @@
+use std::{fs::read_to_string, io::stderr, path::Path};
+
+use crossterm::{
+    execute,
+    style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
+};
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FormatConfig {
+    #[serde(alias = "tab", rename = "tab-size")]
+    pub tab_size: Option<usize>,
+
+    #[serde(alias = "width", rename = "max-width")]
+    pub max_width: Option<usize>,
+
+    #[serde(alias = "args")]
+    pub arguments: Option<ArgumentsLayout>,
+
+    #[serde(rename = "macro-bodies")]
+    pub macro_bodies: Option<MacroBodies>,
+
+    #[serde(rename = "indent-directives")]
+    pub indent_directives: Option<DirectiveIndent>,
+
+    #[serde(rename = "sort-includes")]
+    pub sort_includes: Option<bool>,
+
+    #[serde(rename = "use-tabs")]
+    pub use_tabs: Option<bool>,
+
+    pub braces: Option<BraceStyle>,
+
+    #[serde(rename = "pointer-alignment")]
+    pub pointer_alignment: Option<PointerAlignment>,
+
+    #[serde(rename = "max-empty-lines")]
+    pub max_empty_lines: Option<usize>,
+
+    #[serde(rename = "align-assignments")]
+    pub align_assignments: Option<bool>,
+
+    #[serde(rename = "align-declarations")]
+    pub align_declarations: Option<bool>,
+
+    #[serde(rename = "align-macros")]
+    pub align_macros: Option<bool>,
+
+    #[serde(rename = "align-comments")]
+    pub align_comments: Option<bool>,
+}
+
+impl FormatConfig {
+    /// Fill the unset options with the ones of `other`.
+    pub fn merge(&mut self, other: FormatConfig) {
+        self.tab_size = self.tab_size.or(other.tab_size);
+        self.max_width = self.max_width.or(other.max_width);
+        self.arguments = self.arguments.or(other.arguments);
+        self.macro_bodies = self.macro_bodies.or(other.macro_bodies);
+        self.indent_directives = self.indent_directives.or(other.indent_directives);
+        self.sort_includes = self.sort_includes.or(other.sort_includes);
+        self.use_tabs = self.use_tabs.or(other.use_tabs);
+        self.braces = self.braces.or(other.braces);
+        self.pointer_alignment = self.pointer_alignment.or(other.pointer_alignment);
+        self.max_empty_lines = self.max_empty_lines.or(other.max_empty_lines);
+        self.align_assignments = self.align_assignments.or(other.align_assignments);
+        self.align_declarations = self.align_declarations.or(other.align_declarations);
+        self.align_macros = self.align_macros.or(other.align_macros);
+        self.align_comments = self.align_comments.or(other.align_comments);
+    }
+
+    /// Whether no option is set.
+    pub fn is_empty(&self) -> bool {
+        self.tab_size.is_none()
+            && self.max_width.is_none()
+            && self.arguments.is_none()
+            && self.macro_bodies.is_none()
+            && self.indent_directives.is_none()
+            && self.sort_includes.is_none()
+            && self.use_tabs.is_none()
+            && self.braces.is_none()
+            && self.pointer_alignment.is_none()
+            && self.max_empty_lines.is_none()
+            && self.align_assignments.is_none()
+            && self.align_declarations.is_none()
+            && self.align_macros.is_none()
+            && self.align_comments.is_none()
+    }
+
+    /// Load the options of the `.clang-format` file of `project_path` or of its closest parent.
+    ///
+    /// Only the common keys are read, a warning lists the other ones.
+    pub fn load_clang_format(project_path: &Path) -> Option<Self> {
+        let path = project_path
+            .ancestors()
+            .flat_map(|path| [path.join(".clang-format"), path.join("_clang-format")])
+            .find(|path| path.is_file())?;
+        let code = read_to_string(&path).ok()?;
+        let mut format_config = Self::default();
+        let mut unsupported_keys = Vec::new();
+
+        // A file can have one document per language, the C and C++ one has no `Language` or `Cpp`
+        let style = serde_yaml_ng::Deserializer::from_str(&code)
+            .filter_map(|document| Value::deserialize(document).ok())
+            .find(|style| {
+                style
+                    .get("Language")
+                    .is_none_or(|language| language.as_str() == Some("Cpp"))
+            });
+
+        let Some(Value::Mapping(style)) = style else {
+            Self::warn(&path, "no C or C++ style found".to_string());
+            return None;
+        };
+
+        for (key, value) in style.iter() {
+            let Some(key) = key.as_str() else {
+                continue;
+            };
+            let is_supported = match (key, value) {
+                ("Language", _) => true,
+                ("IndentWidth", Value::Number(width)) => {
+                    format_config.tab_size = width.as_u64().map(|width| width as usize);
+                    format_config.tab_size.is_some()
+                }
+                ("UseTab", Value::Bool(use_tabs)) => {
+                    format_config.use_tabs = Some(*use_tabs);
+                    true
+                }
+                ("UseTab", Value::String(use_tab)) => {
+                    format_config.use_tabs = Some(use_tab != "Never");
+                    true
+                }
+                ("BreakBeforeBraces", Value::String(braces)) => {
+                    format_config.braces = match braces.as_str() {
+                        "Attach" => Some(BraceStyle::Attach),
+                        "Allman" => Some(BraceStyle::Allman),
+                        _ => None,
+                    };
+                    format_config.braces.is_some()
+                }
+                ("ColumnLimit", Value::Number(width)) => {
+                    format_config.max_width = width.as_u64().map(|width| width as usize);
+                    format_config.max_width.is_some()
+                }
+                ("PointerAlignment", Value::String(alignment)) => {
+                    format_config.pointer_alignment = match alignment.as_str() {
+                        "Left" => Some(PointerAlignment::Left),
+                        "Right" => Some(PointerAlignment::Right),
+                        "Middle" => Some(PointerAlignment::Middle),
+                        _ => None,
+                    };
+                    format_config.pointer_alignment.is_some()
+                }
+                ("SortIncludes", Value::Bool(sort_includes)) => {
+                    format_config.sort_includes = Some(*sort_includes);
+                    true
+                }
+                ("SortIncludes", Value::String(sort_includes)) => {
+                    format_config.sort_includes = Some(sort_includes != "Never");
+                    true
+                }
+                ("MaxEmptyLinesToKeep", Value::Number(count)) => {
+                    format_config.max_empty_lines = count.as_u64().map(|count| count as usize);
+                    format_config.max_empty_lines.is_some()
+                }
+                ("AlignConsecutiveAssignments", _) => {
+                    format_config.align_assignments = Self::clang_format_alignment(value);
+                    format_config.align_assignments.is_some()
+                }
+                ("AlignConsecutiveDeclarations", _) => {
+                    format_config.align_declarations = Self::clang_format_alignment(value);
+                    format_config.align_declarations.is_some()
+                }
+                ("AlignConsecutiveMacros", _) => {
+                    format_config.align_macros = Self::clang_format_alignment(value);
+                    format_config.align_macros.is_some()
+                }
+                ("AlignTrailingComments", Value::Bool(align_comments)) => {
+                    format_config.align_comments = Some(*align_comments);
+                    true
+                }
+                ("AlignTrailingComments", Value::Mapping(options)) => {
+                    format_config.align_comments = options
+                        .get("Kind")
+                        .and_then(Value::as_str)
+                        .map(|kind| kind == "Always");
+                    format_config.align_comments.is_some()
+                }
+                _ => false,
+            };
+
+            if !is_supported {
+                unsupported_keys.push(key.to_string());
+            }
+        }
+
+        if !unsupported_keys.is_empty() {
+            Self::warn(
+                &path,
+                format!("unsupported keys ignored : {}", unsupported_keys.join(", ")),
+            );
+        }
+
+        Some(format_config)
+    }
+
+    /// Whether an `AlignConsecutive...` option is enabled, written as a bool, a string or a mapping.
+    fn clang_format_alignment(value: &Value) -> Option<bool> {
+        match value {
+            Value::Bool(enabled) => Some(*enabled),
+            Value::String(kind) => Some(kind != "None"),
+            Value::Mapping(options) => options.get("Enabled").and_then(Value::as_bool),
+            _ => None,
+        }
+    }
+
+    fn warn(path: &Path, message: String) {
+        let _ = execute!(
+            stderr(),
+            SetForegroundColor(Color::DarkYellow),
+            Print("Warning ".bold()),
+            ResetColor,
+            Print(path.to_string_lossy().bold()),
+            Print(format!(" : {message}\n")),
+        );
+    }
+}
+
+/// Where the opening brace of a block goes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum BraceStyle {
+    /// On its own line
+    #[default]
+    Allman,
+    /// At the end of the line before it
+    Attach,
+}
+
+/// Where the `*` and `&` of a declaration go.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PointerAlignment {
+    /// `int* a`
+    Left,
+    /// `int *a`
+    #[default]
+    Right,
+    /// `int * a`
+    Middle,
+}
+
+/// How the arguments of a call or a declaration are laid out when they don't fit on one line.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArgumentsLayout {
+    #[default]
+    BinPack,
+    OnePerLine,
+}
+
+/// How the lines of a multi-line `#define` are laid out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MacroBodies {
+    /// Keep the macro body as written
+    #[default]
+    Keep,
+    /// Keep the macro body but align the continuation backslashes
+    Align,
+}
+
+/// How the preprocessor directives nested in conditional blocks are indented.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirectiveIndent {
+    #[default]
+    None,
+    /// `    #define`
+    BeforeHash,
+    /// `#    define`
+    AfterHash,
+}
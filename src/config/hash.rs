@@ -7,22 +7,19 @@ use std::{
 use blake3::Hash;
 use hashbrown::HashMap;
 
-fn get_hash_path(project_path: &Path, release: bool) -> PathBuf {
-    project_path.join(format!(
-        ".maky/{}_hash",
-        release.then_some("release").unwrap_or("debug")
-    ))
+fn get_hash_path(project_path: &Path, cache_name: &str) -> PathBuf {
+    project_path.join(format!(".maky/{cache_name}_hash"))
 }
 
 pub trait LoadHash {
-    fn load(path: &Path, release: bool) -> io::Result<Self>
+    fn load(path: &Path, cache_name: &str) -> io::Result<Self>
     where
         Self: Sized;
 }
 
 impl LoadHash for HashMap<PathBuf, Hash> {
-    fn load(project_path: &Path, release: bool) -> io::Result<Self> {
-        let hash_file = read_to_string(get_hash_path(project_path, release))?;
+    fn load(project_path: &Path, cache_name: &str) -> io::Result<Self> {
+        let hash_file = read_to_string(get_hash_path(project_path, cache_name))?;
         let mut hash_hashmap = HashMap::new();
         let mut hash_path = Path::new("");
 
@@ -41,11 +38,11 @@ impl LoadHash for HashMap<PathBuf, Hash> {
 }
 
 pub trait SaveHash {
-    fn save(&self, path: &Path, release: bool) -> io::Result<()>;
+    fn save(&self, path: &Path, cache_name: &str) -> io::Result<()>;
 }
 
 impl SaveHash for HashMap<PathBuf, Hash> {
-    fn save(&self, project_path: &Path, release: bool) -> io::Result<()> {
+    fn save(&self, project_path: &Path, cache_name: &str) -> io::Result<()> {
         let mut data = Vec::new();
 
         for hash in self {
@@ -64,6 +61,6 @@ impl SaveHash for HashMap<PathBuf, Hash> {
             );
         }
 
-        write(get_hash_path(project_path, release), data)
+        write(get_hash_path(project_path, cache_name), data)
     }
 }
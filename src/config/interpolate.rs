@@ -0,0 +1,96 @@
+use std::env;
+
+use hashbrown::HashMap;
+
+use super::error::closest;
+
+/// Variable of a config value that can't be expanded.
+#[derive(Debug)]
+pub struct InterpolationError {
+    /// The `${...}` expression as written
+    pub expression: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+/// Expand the `${name}` variables of `value`, with `${env:VAR}` and `${env:VAR:-default}` reading
+/// the environment, and `$$` standing for `$`.
+pub fn interpolate(
+    value: &str,
+    variables: &HashMap<&str, String>,
+) -> Result<String, InterpolationError> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        let Some(inner) = rest.strip_prefix("${") else {
+            expanded.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let Some(end) = inner.find('}') else {
+            return Err(InterpolationError {
+                expression: rest.to_string(),
+                message: format!("unterminated variable `{rest}`"),
+                suggestion: None,
+            });
+        };
+        let name = &inner[..end];
+        let expression = format!("${{{name}}}");
+
+        if let Some(env_name) = name.strip_prefix("env:") {
+            let (env_name, default) = match env_name.split_once(":-") {
+                Some((env_name, default)) => (env_name, Some(default)),
+                None => (env_name, None),
+            };
+
+            match (env::var(env_name), default) {
+                (Ok(env_value), _) => expanded.push_str(&env_value),
+                (Err(_), Some(default)) => expanded.push_str(default),
+                (Err(_), None) => {
+                    return Err(InterpolationError {
+                        message: format!(
+                            "environment variable `{env_name}` of `{expression}` is not set"
+                        ),
+                        expression,
+                        suggestion: None,
+                    })
+                }
+            }
+        } else if let Some(variable) = variables.get(name) {
+            expanded.push_str(variable);
+        } else {
+            let mut names: Vec<&str> = variables.keys().copied().collect();
+
+            names.sort();
+
+            return Err(InterpolationError {
+                message: format!(
+                    "undefined variable `{expression}`, expected `env:NAME` or one of {}",
+                    names
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                suggestion: closest(name, names.into_iter()),
+                expression,
+            });
+        }
+
+        rest = &inner[end + 1..];
+    }
+
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
@@ -6,6 +6,7 @@ use serde_with::{formats::PreferOne, serde_as, OneOrMany};
 
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LibConfig {
     #[serde(default = "LibConfig::default_vec")]
     #[serde(alias = "lib")]
@@ -28,6 +29,14 @@ pub struct LibConfig {
 }
 
 impl LibConfig {
+    /// Merge `other` over this library, its lists extending these ones.
+    pub fn merge(&mut self, other: LibConfig) {
+        self.library.extend(other.library);
+        self.directories.extend(other.directories);
+        self.includes.extend(other.includes);
+        self.pkg_config.extend(other.pkg_config);
+    }
+
     fn default_vec<T>() -> Vec<T> {
         Vec::new()
     }
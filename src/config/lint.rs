@@ -0,0 +1,131 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Headers must have an include guard or `#pragma once`
+    #[serde(rename = "include-guards")]
+    pub include_guards: Severity,
+
+    /// Names must follow the cases of `[lint.names]`
+    pub naming: Severity,
+
+    /// The functions of `banned` must not be called
+    #[serde(rename = "banned-functions")]
+    pub banned_functions: Severity,
+
+    /// Files marked with `//@main` must only define static helper functions
+    #[serde(rename = "main-helpers")]
+    pub main_helpers: Severity,
+
+    /// `TODO`s must have an owner, like `TODO(name)`
+    #[serde(rename = "todo-owner")]
+    pub todo_owner: Severity,
+
+    /// Kind of include guard added by `maky fix guards`
+    #[serde(rename = "guard-style")]
+    pub guard_style: GuardStyle,
+
+    pub banned: Vec<String>,
+
+    pub names: NamingConfig,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            include_guards: Severity::Warn,
+            naming: Severity::Warn,
+            banned_functions: Severity::Error,
+            main_helpers: Severity::Warn,
+            todo_owner: Severity::Warn,
+            guard_style: GuardStyle::default(),
+            banned: ["gets", "strcpy", "strcat", "sprintf", "vsprintf"]
+                .map(String::from)
+                .to_vec(),
+            names: NamingConfig::default(),
+        }
+    }
+}
+
+/// Case of each kind of name, unset kinds are not checked.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct NamingConfig {
+    pub functions: Option<Case>,
+    pub types: Option<Case>,
+    pub macros: Option<Case>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Off,
+    Warn,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuardStyle {
+    /// `#ifndef`, `#define` and `#endif` of a macro named after the header
+    #[default]
+    Ifndef,
+    /// `#pragma once`
+    PragmaOnce,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    /// `snake_case`
+    #[serde(rename = "snake-case")]
+    Snake,
+    /// `camelCase`
+    #[serde(rename = "camel-case")]
+    Camel,
+    /// `PascalCase`
+    #[serde(rename = "pascal-case")]
+    Pascal,
+    /// `UPPER_CASE`
+    #[serde(rename = "upper-case")]
+    Upper,
+}
+
+impl Case {
+    /// Whether `name` follows the case, leading and trailing underscores are allowed.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim_matches('_');
+        let mut chars = name.chars();
+        let first = chars.next();
+
+        match self {
+            Case::Snake => name
+                .chars()
+                .all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '_'),
+            Case::Upper => name
+                .chars()
+                .all(|char| char.is_ascii_uppercase() || char.is_ascii_digit() || char == '_'),
+            Case::Camel => {
+                first.is_none_or(|char| char.is_ascii_lowercase())
+                    && chars.all(|char| char.is_ascii_alphanumeric())
+            }
+            Case::Pascal => {
+                first.is_none_or(|char| char.is_ascii_uppercase())
+                    && chars.all(|char| char.is_ascii_alphanumeric())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Case::Snake => "snake_case",
+            Case::Camel => "camelCase",
+            Case::Pascal => "PascalCase",
+            Case::Upper => "UPPER_CASE",
+        })
+    }
+}
@@ -1,16 +1,33 @@
 use std::path::{Path, PathBuf};
 
+use hashbrown::HashSet;
 use semver::Version;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::{formats::PreferOne, serde_as, OneOrMany};
 
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct PackageConfig {
+    pub name: Option<String>,
+
+    /// Required by the project config, extended configs can't set it
+    #[serde(default = "PackageConfig::default_version")]
     #[serde(serialize_with = "PackageConfig::serialize_version")]
     #[serde(deserialize_with = "PackageConfig::deserialize_version")]
     pub version: Version,
 
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub authors: Vec<String>,
+
+    pub license: Option<String>,
+
+    pub homepage: Option<String>,
+
+    pub repository: Option<String>,
+
     #[serde(default = "PackageConfig::default_c_compiler")]
     #[serde(alias = "cc", rename = "c-compiler")]
     pub c_compiler: String,
@@ -42,6 +59,128 @@ pub struct PackageConfig {
 }
 
 impl PackageConfig {
+    /// Name of the package, or the name of its folder when it has none.
+    pub fn get_name(&self, project_path: &Path) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            project_path
+                .canonicalize()
+                .unwrap_or(project_path.to_path_buf())
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Metadata of the package by key, the ones not set are left out.
+    pub fn get_metadata(&self, project_path: &Path) -> Vec<(&'static str, String)> {
+        let mut metadata = vec![
+            ("name", self.get_name(project_path)),
+            ("version", self.version.to_string()),
+        ];
+
+        if let Some(description) = &self.description {
+            metadata.push(("description", description.clone()));
+        }
+        if !self.authors.is_empty() {
+            metadata.push(("authors", self.authors.join(", ")));
+        }
+        if let Some(license) = &self.license {
+            metadata.push(("license", license.clone()));
+        }
+        if let Some(homepage) = &self.homepage {
+            metadata.push(("homepage", homepage.clone()));
+        }
+        if let Some(repository) = &self.repository {
+            metadata.push(("repository", repository.clone()));
+        }
+
+        metadata
+    }
+
+    /// Name of the field set by the `key` of a [package] table, which can be an alias.
+    pub fn field_name(key: &str) -> Option<&'static str> {
+        Some(match key {
+            "name" => "name",
+            "version" => "version",
+            "description" => "description",
+            "authors" => "authors",
+            "license" => "license",
+            "homepage" => "homepage",
+            "repository" => "repository",
+            "c-compiler" | "cc" => "c-compiler",
+            "cpp-compiler" | "cxx" => "cpp-compiler",
+            "standard" | "std" => "standard",
+            "binaries" | "bin" => "binaries",
+            "objects" | "obj" => "objects",
+            "sources" | "src" => "sources",
+            "includes" | "inc" => "includes",
+            _ => return None,
+        })
+    }
+
+    /// Take the fields of `other` its config sets, the `package.<field>` ones among `sections`.
+    pub fn merge(&mut self, other: PackageConfig, sections: &HashSet<String>) {
+        let PackageConfig {
+            name,
+            version,
+            description,
+            authors,
+            license,
+            homepage,
+            repository,
+            c_compiler,
+            cpp_compiler,
+            standard,
+            binaries,
+            objects,
+            sources,
+            includes,
+        } = other;
+
+        if sections.contains("package.name") {
+            self.name = name;
+        }
+        if sections.contains("package.version") {
+            self.version = version;
+        }
+        if sections.contains("package.description") {
+            self.description = description;
+        }
+        if sections.contains("package.authors") {
+            self.authors = authors;
+        }
+        if sections.contains("package.license") {
+            self.license = license;
+        }
+        if sections.contains("package.homepage") {
+            self.homepage = homepage;
+        }
+        if sections.contains("package.repository") {
+            self.repository = repository;
+        }
+        if sections.contains("package.c-compiler") {
+            self.c_compiler = c_compiler;
+        }
+        if sections.contains("package.cpp-compiler") {
+            self.cpp_compiler = cpp_compiler;
+        }
+        if sections.contains("package.standard") {
+            self.standard = standard;
+        }
+        if sections.contains("package.binaries") {
+            self.binaries = binaries;
+        }
+        if sections.contains("package.objects") {
+            self.objects = objects;
+        }
+        if sections.contains("package.sources") {
+            self.sources = sources;
+        }
+        if sections.contains("package.includes") {
+            self.includes = includes;
+        }
+    }
+
     fn serialize_version<S>(version: &Version, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -55,30 +194,35 @@ impl PackageConfig {
     {
         let version = String::deserialize(deserializer)?;
 
-        Ok(Version::parse(&version).expect("Malformed version in config"))
+        Version::parse(&version)
+            .map_err(|error| de::Error::custom(format!("malformed version `{version}`, {error}")))
+    }
+
+    fn default_version() -> Version {
+        Version::new(0, 0, 0)
     }
 
-    fn default_c_compiler() -> String {
+    pub fn default_c_compiler() -> String {
         "gcc".to_string()
     }
 
-    fn default_cpp_compiler() -> String {
+    pub fn default_cpp_compiler() -> String {
         "g++".to_string()
     }
 
-    fn default_binaries() -> PathBuf {
+    pub fn default_binaries() -> PathBuf {
         Path::new("bin").to_path_buf()
     }
 
-    fn default_objects() -> PathBuf {
+    pub fn default_objects() -> PathBuf {
         Path::new("obj").to_path_buf()
     }
 
-    fn default_sources() -> Vec<PathBuf> {
+    pub fn default_sources() -> Vec<PathBuf> {
         vec![Path::new("src").to_path_buf()]
     }
 
-    fn default_includes() -> Vec<PathBuf> {
+    pub fn default_includes() -> Vec<PathBuf> {
         vec![Path::new("include").to_path_buf()]
     }
 }
@@ -0,0 +1,196 @@
+use std::env;
+
+use hashbrown::HashSet;
+
+use super::features::{get_cpu_features, get_features, get_target_features};
+
+/// Operating systems recognized in target triples, `darwin` standing for `macos`.
+const OSS: [&str; 13] = [
+    "android",
+    "ios",
+    "macos",
+    "darwin",
+    "linux",
+    "windows",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+    "wasi",
+];
+
+/// Operating system, family, architecture, CPU features and profile the specific configs are
+/// selected for.
+#[derive(Debug, Clone)]
+pub struct Platform {
+    /// Target triple, like `x86_64-unknown-linux-gnu`
+    pub target: String,
+    pub os: String,
+    pub family: String,
+    pub arch: String,
+    /// ABI or C library, like `gnu`, `musl` or `msvc`, empty when unknown
+    pub env: String,
+    pub features: HashSet<String>,
+    /// Features of the `[features]` table, the ones asked for until the config resolves them
+    pub user_features: HashSet<String>,
+    /// Whether the `default` features are enabled
+    pub default_features: bool,
+    /// `dev` or `release`
+    pub profile: String,
+    /// CPU given to the compiler with `-march`
+    pub cpu: Option<String>,
+    /// Whether the platform is a target given instead of the host, built with its toolchain into
+    /// folders of its own
+    pub cross: bool,
+}
+
+impl Platform {
+    pub fn host() -> Self {
+        let os = env::consts::OS;
+        let arch = env::consts::ARCH;
+        let env = if cfg!(target_env = "gnu") {
+            "gnu"
+        } else if cfg!(target_env = "musl") {
+            "musl"
+        } else if cfg!(target_env = "msvc") {
+            "msvc"
+        } else {
+            ""
+        };
+        let target = match os {
+            "macos" => format!("{arch}-apple-darwin"),
+            "windows" => format!("{arch}-pc-windows-{env}"),
+            _ if env.is_empty() => format!("{arch}-unknown-{os}"),
+            _ => format!("{arch}-unknown-{os}-{env}"),
+        };
+
+        Self {
+            target,
+            os: os.to_string(),
+            family: env::consts::FAMILY.to_string(),
+            arch: arch.to_string(),
+            env: env.to_string(),
+            features: get_features()
+                .into_iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+            user_features: HashSet::new(),
+            default_features: true,
+            profile: "dev".to_string(),
+            cpu: None,
+            cross: false,
+        }
+    }
+
+    /// Platform of a target triple, like `aarch64-unknown-linux-gnu`, `x86_64-pc-windows-msvc` or
+    /// `x86_64-w64-mingw32`.
+    ///
+    /// Only the features every CPU of the architecture has are enabled.
+    pub fn from_target(target: &str) -> Self {
+        let components: Vec<&str> = target.split('-').collect();
+        let arch = match components[0] {
+            "i386" | "i586" | "i686" => "x86",
+            "arm64" => "aarch64",
+            arch if arch.starts_with("armv") || arch.starts_with("thumb") => "arm",
+            arch => arch,
+        };
+        let os = if components[1..]
+            .iter()
+            .any(|component| component.starts_with("mingw"))
+        {
+            "windows"
+        } else {
+            OSS.iter()
+                .find(|os| components[1..].contains(os))
+                .map_or("none", |os| if *os == "darwin" { "macos" } else { os })
+        };
+        let env = components[1..]
+            .iter()
+            .find_map(|component| match *component {
+                component if component.starts_with("gnu") || component.starts_with("mingw") => {
+                    Some("gnu")
+                }
+                component if component.starts_with("musl") => Some("musl"),
+                "msvc" => Some("msvc"),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let family = match os {
+            "windows" => "windows",
+            "none" | "wasi" => "",
+            _ => "unix",
+        };
+
+        Self {
+            target: target.to_string(),
+            os: os.to_string(),
+            family: family.to_string(),
+            arch: arch.to_string(),
+            env: env.to_string(),
+            features: get_target_features(arch)
+                .into_iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+            user_features: HashSet::new(),
+            default_features: true,
+            profile: "dev".to_string(),
+            cpu: None,
+            cross: true,
+        }
+    }
+
+    /// Name of the environment variable `name` for this platform, like `CC`, or
+    /// `CC_aarch64_linux_gnu` for a cross build, as the ones without target are for the host.
+    pub fn env_variable(&self, name: &str) -> String {
+        if self.cross {
+            format!("{name}_{}", self.target.replace('-', "_"))
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Override the CPU features with the ones of `cpu`, then with `features` like `+avx2` to
+    /// enable or `-avx2` to disable one.
+    ///
+    /// `native` is the CPU Maky runs on, and a CPU Maky doesn't know keeps the current features.
+    pub fn set_target_cpu(&mut self, cpu: Option<&str>, features: &[String]) -> Result<(), String> {
+        if let Some(cpu) = cpu {
+            let cpu_features = if cpu == "native" {
+                Some(get_features())
+            } else {
+                get_cpu_features(cpu)
+            };
+
+            if let Some(cpu_features) = cpu_features {
+                self.features = cpu_features
+                    .into_iter()
+                    .map(|feature| feature.to_string())
+                    .collect();
+            }
+
+            self.cpu = Some(cpu.to_string());
+        }
+
+        for feature in features.iter() {
+            let (enable, name) = match feature.split_at_checked(1) {
+                Some(("-", name)) => (false, name),
+                Some(("+", name)) => (true, name),
+                _ => (true, feature.as_str()),
+            };
+
+            if name.is_empty() {
+                return Err(format!("malformed target feature `{feature}`"));
+            }
+
+            if enable {
+                self.features.insert(name.to_string());
+            } else {
+                self.features.remove(name);
+            }
+        }
+
+        Ok(())
+    }
+}
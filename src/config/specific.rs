@@ -4,10 +4,11 @@ use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use serde_with::{formats::PreferOne, serde_as, OneOrMany};
 
-use super::lib::LibConfig;
+use super::{lib::LibConfig, merge_entries};
 
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct SpecificConfig {
     #[serde(alias = "cc", rename = "c-compiler")]
     pub c_compiler: Option<String>,
@@ -32,3 +33,30 @@ pub struct SpecificConfig {
     #[serde(alias = "libs")]
     pub libraries: Option<HashMap<String, LibConfig>>,
 }
+
+impl SpecificConfig {
+    /// Merge `other` over this config, its values replacing these ones and its lists extending
+    /// them.
+    pub fn merge(&mut self, other: SpecificConfig) {
+        fn extend<T>(list: &mut Option<Vec<T>>, other: Option<Vec<T>>) {
+            match (list.as_mut(), other) {
+                (Some(list), Some(other)) => list.extend(other),
+                (None, other) => *list = other,
+                _ => {}
+            }
+        }
+
+        self.c_compiler = other.c_compiler.or(self.c_compiler.take());
+        self.cpp_compiler = other.cpp_compiler.or(self.cpp_compiler.take());
+        self.binaries = other.binaries.or(self.binaries.take());
+        self.objects = other.objects.or(self.objects.take());
+        extend(&mut self.sources, other.sources);
+        extend(&mut self.includes, other.includes);
+
+        match (self.libraries.as_mut(), other.libraries) {
+            (Some(libraries), Some(other)) => merge_entries(libraries, other, LibConfig::merge),
+            (None, other) => self.libraries = other,
+            _ => {}
+        }
+    }
+}
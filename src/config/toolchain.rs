@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::platform::Platform;
+
+/// Tools and file names of a target, from a `[toolchain.'<target>']` table or a
+/// `maky/toolchains/<target>.toml` file next to the user config.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ToolchainConfig {
+    #[serde(alias = "cc", rename = "c-compiler")]
+    pub c_compiler: Option<String>,
+
+    #[serde(alias = "cxx", rename = "cpp-compiler")]
+    pub cpp_compiler: Option<String>,
+
+    /// Root of the headers and libraries of the target, given to the compiler with `--sysroot`
+    pub sysroot: Option<PathBuf>,
+
+    #[serde(alias = "ar")]
+    pub archiver: Option<String>,
+
+    /// Extension of the executables without the dot, like `exe`
+    #[serde(alias = "exe-extension", rename = "executable-extension")]
+    pub executable_extension: Option<String>,
+
+    /// Extension of the shared libraries without the dot, like `so`, `dll` or `dylib`
+    #[serde(alias = "lib-extension", rename = "library-extension")]
+    pub library_extension: Option<String>,
+
+    #[serde(alias = "lib-prefix", rename = "library-prefix")]
+    pub library_prefix: Option<String>,
+}
+
+impl ToolchainConfig {
+    pub const KEYS: [&'static str; 7] = [
+        "c-compiler",
+        "cpp-compiler",
+        "sysroot",
+        "archiver",
+        "executable-extension",
+        "library-extension",
+        "library-prefix",
+    ];
+
+    /// Toolchain of what a definition leaves unset, with the file names of the os of `platform`.
+    ///
+    /// A target uses the GNU cross toolchain named after it, like `aarch64-linux-gnu-gcc`, while
+    /// the host keeps the compilers of the config.
+    pub fn default_for(platform: &Platform) -> Self {
+        let (executable_extension, library_extension, library_prefix) = match platform.os.as_str() {
+            "windows" => ("exe", "dll", ""),
+            "macos" | "ios" => ("", "dylib", "lib"),
+            _ => ("", "so", "lib"),
+        };
+        let tool = |name: &str| {
+            if platform.cross {
+                format!("{}-{name}", platform.target)
+            } else {
+                name.to_string()
+            }
+        };
+
+        Self {
+            c_compiler: platform.cross.then(|| tool("gcc")),
+            cpp_compiler: platform.cross.then(|| tool("g++")),
+            sysroot: None,
+            archiver: Some(tool("ar")),
+            executable_extension: Some(executable_extension.to_string()),
+            library_extension: Some(library_extension.to_string()),
+            library_prefix: Some(library_prefix.to_string()),
+        }
+    }
+
+    /// Values of this toolchain, or of `other` for the ones it doesn't set.
+    pub fn or(self, other: ToolchainConfig) -> Self {
+        Self {
+            c_compiler: self.c_compiler.or(other.c_compiler),
+            cpp_compiler: self.cpp_compiler.or(other.cpp_compiler),
+            sysroot: self.sysroot.or(other.sysroot),
+            archiver: self.archiver.or(other.archiver),
+            executable_extension: self.executable_extension.or(other.executable_extension),
+            library_extension: self.library_extension.or(other.library_extension),
+            library_prefix: self.library_prefix.or(other.library_prefix),
+        }
+    }
+
+    /// Value of a key of [`ToolchainConfig::KEYS`], as text.
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "c-compiler" => self.c_compiler.clone(),
+            "cpp-compiler" => self.cpp_compiler.clone(),
+            "sysroot" => self
+                .sysroot
+                .as_ref()
+                .map(|sysroot| sysroot.to_string_lossy().to_string()),
+            "archiver" => self.archiver.clone(),
+            "executable-extension" => self.executable_extension.clone(),
+            "library-extension" => self.library_extension.clone(),
+            "library-prefix" => self.library_prefix.clone(),
+            _ => None,
+        }
+    }
+}
@@ -36,7 +36,7 @@ pub fn compile(
 
             if is_code_file(extension) {
                 new_hash_hashmap_clone.remove(new_hash.0);
-                files_to_compile.insert(new_hash.0.clone(), new_hash.1.clone());
+                files_to_compile.insert(new_hash.0.clone(), *new_hash.1);
             }
         }
     }
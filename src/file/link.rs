@@ -13,6 +13,8 @@ use crate::config::ProjectConfig;
 
 use super::get_includes;
 
+pub type FileToLink = (PathBuf, bool, Option<String>, HashSet<PathBuf>);
+
 pub fn link(
     project_path: &Path,
     project_config: &ProjectConfig,
@@ -21,7 +23,7 @@ pub fn link(
     files_to_compile: &HashMap<PathBuf, Hash>,
     h_c_link: &HashMap<PathBuf, HashSet<PathBuf>>,
     c_h_link: &HashMap<PathBuf, HashSet<PathBuf>>,
-) -> anyhow::Result<Vec<(PathBuf, bool, Option<String>, HashSet<PathBuf>)>> {
+) -> anyhow::Result<Vec<FileToLink>> {
     let h_c_link_filtered = filter_h_c_link(h_c_link)?;
     let mut files_to_link = Vec::new();
 
@@ -81,8 +83,17 @@ pub fn link(
         link_files(main_file, false, main_name.clone())?;
     }
 
+    // A single library without name is named after the package
+    let package_name = project_config
+        .package
+        .as_ref()
+        .unwrap()
+        .name
+        .clone()
+        .filter(|_| lib_hashmap.values().filter(|name| name.is_none()).count() == 1);
+
     for (lib_file, lib_name) in lib_hashmap.iter() {
-        link_files(lib_file, true, lib_name.clone())?;
+        link_files(lib_file, true, lib_name.clone().or(package_name.clone()))?;
     }
 
     Ok(files_to_link)
@@ -208,10 +219,10 @@ fn get_h_prototypes(code: &str) -> anyhow::Result<HashSet<Declaration>> {
 
 fn get_c_prototypes(code: &str) -> anyhow::Result<HashSet<Declaration>> {
     let mut prototype_hashset = HashSet::new();
-    let mut pretokenizer = Pretokenizer::new(code);
+    let pretokenizer = Pretokenizer::new(code);
     let mut pretoken_vec: Vec<&str> = Vec::new();
 
-    while let Some(Pretoken { s, .. }) = pretokenizer.next() {
+    for Pretoken { s, .. } in pretokenizer {
         if s.ends_with("{") {
             let prototype = {
                 let mut parenthesis_count = 0;
@@ -260,18 +271,12 @@ fn get_c_prototypes(code: &str) -> anyhow::Result<HashSet<Declaration>> {
     Ok(prototype_hashset)
 }
 
-fn clear_prototype(s: &String, prototype: &mut Vec<String>) {
+fn clear_prototype(s: &str, prototype: &mut [String]) {
     if let Some(last_s) = prototype.last_mut() {
         if (last_s.ends_with(",") || last_s.ends_with(")") || last_s.ends_with(");"))
             && !(s.ends_with(",") || s.ends_with("("))
         {
-            last_s.retain(|char| {
-                if let ',' | '*' | '[' | ']' | '(' | ')' | ';' = char {
-                    true
-                } else {
-                    false
-                }
-            });
+            last_s.retain(|char| matches!(char, ',' | '*' | '[' | ']' | '(' | ')' | ';'));
         }
     }
 }
@@ -16,10 +16,11 @@ pub mod compile;
 pub mod link;
 
 static PATTERN_MATCHER: LazyLock<AhoCorasick> = LazyLock::new(|| {
-    AhoCorasick::new(&["//@main", "//@lib", "//@import "])
+    AhoCorasick::new(["//@main", "//@lib", "//@import "])
         .expect("Failed to initialize AhoCorasick pattern matcher")
 });
 
+#[allow(clippy::too_many_arguments)]
 pub fn scan_dir(
     project_path: &Path,
     project_config: &ProjectConfig,
@@ -32,90 +33,88 @@ pub fn scan_dir(
     c_h_link: &mut HashMap<PathBuf, HashSet<PathBuf>>,
     hash_hashmap: &mut HashMap<PathBuf, Hash>,
 ) -> anyhow::Result<()> {
-    for entry in read_dir(dir_path)? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-
-            if path.is_file() {
-                let extension = path.extension().unwrap_or_default();
-
-                if is_code_file(extension) || is_header_file(extension) {
-                    let code = &read_to_string(&path).map_err(|error| {
-                        io::Error::new(
-                            error.kind(),
-                            format!("{} : {}", path.to_string_lossy(), error),
-                        )
-                    })?;
-                    let includes = get_includes(
-                        &path,
-                        project_path,
-                        &project_config.package.as_ref().unwrap().includes,
-                        code,
-                    );
-
-                    if is_code_file(extension) {
-                        c_h_link.insert(path.clone(), includes.clone());
-
-                        for match_ in PATTERN_MATCHER.find_iter(code) {
-                            let line_option = code[match_.end()..]
-                                .lines()
-                                .next()
-                                .map(|line| line.trim().to_string())
-                                .filter(|line| !line.is_empty());
-
-                            match match_.pattern().as_usize() {
-                                0 => {
-                                    main_hashmap.insert(path.clone(), line_option);
-                                }
-                                1 => {
-                                    lib_hashmap.insert(path.clone(), line_option);
-                                }
-                                2 => {
-                                    if let Some(line) = line_option {
-                                        import_hashmap.insert(
-                                            path.clone(),
-                                            line.split(",")
-                                                .map(str::trim)
-                                                .map(str::to_string)
-                                                .collect::<Vec<String>>(),
-                                        );
-                                    }
+    for entry in read_dir(dir_path)?.flatten() {
+        let path = entry.path();
+
+        if path.is_file() {
+            let extension = path.extension().unwrap_or_default();
+
+            if is_code_file(extension) || is_header_file(extension) {
+                let code = &read_to_string(&path).map_err(|error| {
+                    io::Error::new(
+                        error.kind(),
+                        format!("{} : {}", path.to_string_lossy(), error),
+                    )
+                })?;
+                let includes = get_includes(
+                    &path,
+                    project_path,
+                    &project_config.package.as_ref().unwrap().includes,
+                    code,
+                );
+
+                if is_code_file(extension) {
+                    c_h_link.insert(path.clone(), includes.clone());
+
+                    for match_ in PATTERN_MATCHER.find_iter(code) {
+                        let line_option = code[match_.end()..]
+                            .lines()
+                            .next()
+                            .map(|line| line.trim().to_string())
+                            .filter(|line| !line.is_empty());
+
+                        match match_.pattern().as_usize() {
+                            0 => {
+                                main_hashmap.insert(path.clone(), line_option);
+                            }
+                            1 => {
+                                lib_hashmap.insert(path.clone(), line_option);
+                            }
+                            2 => {
+                                if let Some(line) = line_option {
+                                    import_hashmap.insert(
+                                        path.clone(),
+                                        line.split(",")
+                                            .map(str::trim)
+                                            .map(str::to_string)
+                                            .collect::<Vec<String>>(),
+                                    );
                                 }
-                                _ => {}
                             }
+                            _ => {}
                         }
                     }
+                }
 
-                    hash_hashmap.insert(path.clone(), hash(code.as_bytes()));
-
-                    for include in includes {
-                        if is_code_file(extension) {
-                            h_c_link
-                                .entry(include)
-                                .or_insert(HashSet::new())
-                                .insert(path.clone());
-                        } else {
-                            h_h_link
-                                .entry(include)
-                                .or_insert(HashSet::new())
-                                .insert(path.clone());
-                        }
+                hash_hashmap.insert(path.clone(), hash(code.as_bytes()));
+
+                for include in includes {
+                    if is_code_file(extension) {
+                        h_c_link
+                            .entry(include)
+                            .or_insert(HashSet::new())
+                            .insert(path.clone());
+                    } else {
+                        h_h_link
+                            .entry(include)
+                            .or_insert(HashSet::new())
+                            .insert(path.clone());
                     }
                 }
-            } else if path.is_dir() {
-                scan_dir(
-                    project_path,
-                    project_config,
-                    &path,
-                    main_hashmap,
-                    lib_hashmap,
-                    import_hashmap,
-                    h_h_link,
-                    h_c_link,
-                    c_h_link,
-                    hash_hashmap,
-                )?;
             }
+        } else if path.is_dir() {
+            scan_dir(
+                project_path,
+                project_config,
+                &path,
+                main_hashmap,
+                lib_hashmap,
+                import_hashmap,
+                h_h_link,
+                h_c_link,
+                c_h_link,
+                hash_hashmap,
+            )?;
         }
     }
 
@@ -125,19 +124,17 @@ pub fn scan_dir(
 pub fn scan_dir_dependency(dir_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
     let mut h_files = Vec::new();
 
-    for entry in read_dir(dir_path)? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
+    for entry in read_dir(dir_path)?.flatten() {
+        let path = entry.path();
 
-            if path.is_file() {
-                let extension = path.extension().unwrap_or_default();
+        if path.is_file() {
+            let extension = path.extension().unwrap_or_default();
 
-                if is_header_file(extension) {
-                    h_files.push(path);
-                }
-            } else if path.is_dir() {
-                h_files.extend(scan_dir_dependency(&path)?);
+            if is_header_file(extension) {
+                h_files.push(path);
             }
+        } else if path.is_dir() {
+            h_files.extend(scan_dir_dependency(&path)?);
         }
     }
 
@@ -149,13 +146,12 @@ const INCLUDE_PATTERN: &str = "#include";
 pub fn get_includes(
     path: &Path,
     project_path: &Path,
-    include_path_vec: &Vec<PathBuf>,
+    include_path_vec: &[PathBuf],
     code: &str,
 ) -> HashSet<PathBuf> {
     let mut include_hashset = HashSet::new();
-    let parent_path = path.parent().unwrap_or(Path::new("./")).to_path_buf();
 
-    'main: for (index, _) in code.match_indices(INCLUDE_PATTERN) {
+    for (index, _) in code.match_indices(INCLUDE_PATTERN) {
         let index = index + INCLUDE_PATTERN.len();
         let code = code[index..]
             .lines()
@@ -164,21 +160,12 @@ pub fn get_includes(
             .trim();
 
         if code.len() > 2 {
-            let path = Path::new(&code[1..code.len() - 1]);
-            let path_with_parent = parent_path.join(path);
-
-            if path_with_parent.is_file() {
-                include_hashset.insert(path_with_parent.to_path_buf());
-                continue 'main;
-            }
-
-            for include_path in include_path_vec {
-                let path = project_path.join(include_path).join(path);
+            let include = Path::new(&code[1..code.len() - 1]);
 
-                if path.is_file() {
-                    include_hashset.insert(path.to_path_buf());
-                    continue 'main;
-                }
+            if let Some(include_path) =
+                resolve_include(path, project_path, include_path_vec, include)
+            {
+                include_hashset.insert(include_path);
             }
         }
     }
@@ -186,6 +173,27 @@ pub fn get_includes(
     include_hashset
 }
 
+/// Find the file included as `include` by the file at `path`, either next to it or in one of the
+/// include directories.
+pub fn resolve_include(
+    path: &Path,
+    project_path: &Path,
+    include_path_vec: &[PathBuf],
+    include: &Path,
+) -> Option<PathBuf> {
+    let parent_path = path.parent().unwrap_or(Path::new("./"));
+    let path_with_parent = parent_path.join(include);
+
+    if path_with_parent.is_file() {
+        return Some(path_with_parent);
+    }
+
+    include_path_vec
+        .iter()
+        .map(|include_path| project_path.join(include_path).join(include))
+        .find(|path| path.is_file())
+}
+
 fn is_code_file(extension: &OsStr) -> bool {
     extension == "c"
         || extension == "cc"
@@ -194,7 +202,7 @@ fn is_code_file(extension: &OsStr) -> bool {
         || extension == "c++"
 }
 
-fn is_header_file(extension: &OsStr) -> bool {
+pub fn is_header_file(extension: &OsStr) -> bool {
     extension == "h"
         || extension == "hh"
         || extension == "hpp"
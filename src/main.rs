@@ -3,12 +3,15 @@ mod config;
 mod file;
 mod pkg_config;
 
-use std::{io::stderr, path::PathBuf};
+use std::{io::stderr, path::PathBuf, process::exit};
 
-use clap::{command, ArgAction, Parser, Subcommand};
-use command::{BuildFlags, FormatOptions};
+use clap::{ArgAction, Parser, Subcommand};
+use command::{BuildFlags, ConfigFormat, FormatFlags, Profile};
+use config::{error::ConfigError, format::FormatConfig, ProjectConfig};
 
-use crate::command::{build, clean, format, init, run};
+use crate::command::{
+    build, clean, config_show, doc, fix_guards, format, format_stdin, init, lint, run,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -42,6 +45,34 @@ enum Commands {
         /// Enable a pretty display
         #[arg(long, action=ArgAction::Set, default_value_t = true)]
         pretty: bool,
+
+        /// Target triple to build for, like `aarch64-linux-gnu` or `x86_64-w64-mingw32`, with its toolchain instead of the host
+        #[arg(long)]
+        target: Option<String>,
+
+        /// CPU to compile for, like `native` or `x86-64-v3`, which selects its features
+        #[arg(long)]
+        target_cpu: Option<String>,
+
+        /// C compiler, before the `CC` environment variable, the specific configs and the package
+        #[arg(long)]
+        cc: Option<String>,
+
+        /// C++ compiler, before the `CXX` environment variable, the specific configs and the package
+        #[arg(long)]
+        cxx: Option<String>,
+
+        /// CPU features to enable like `+avx2` or disable like `-avx2`, separated by commas
+        #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+        target_feature: Vec<String>,
+
+        /// Features of the `[features]` table to enable, separated by commas
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+
+        /// Don't enable the `default` features
+        #[arg(long)]
+        no_default_features: bool,
     },
 
     /// Build files then run the specified file
@@ -75,8 +106,69 @@ enum Commands {
         config_file: String,
 
         /// Tabulation size in spaces
-        #[arg(short = 't', long = "tab", default_value_t = 4)]
-        tab_size: usize,
+        #[arg(short = 't', long = "tab")]
+        tab_size: Option<usize>,
+
+        /// Maximum line width, 0 to never break lines
+        #[arg(short = 'w', long = "max-width")]
+        max_width: Option<usize>,
+
+        /// Sort and group the consecutive includes
+        #[arg(long)]
+        sort_includes: bool,
+
+        /// Only format the lines changed in git
+        #[arg(long)]
+        changed: bool,
+
+        /// Git revision the changed lines are compared to
+        #[arg(long, requires = "changed", default_value_t = ("HEAD").to_string())]
+        base: String,
+
+        /// Format the code read from stdin and write it to stdout
+        #[arg(long, conflicts_with_all = ["files", "changed", "check", "diff"])]
+        stdin: bool,
+
+        /// Name of the file formatted from stdin, to find its project config
+        #[arg(long, requires = "stdin")]
+        assume_filename: Option<String>,
+
+        /// Write nothing, print a diff of the files that would change and exit with an error if any would
+        #[arg(long)]
+        check: bool,
+
+        /// Write nothing and print a diff of the files that would change
+        #[arg(long)]
+        diff: bool,
+    },
+
+    /// Check the bin and lib files of the current project with the lint rules
+    Lint {
+        /// Files to lint
+        files: Vec<String>,
+
+        /// Maky config file or folder
+        #[arg(short = 'f', long = "file", default_value_t = ("./Maky.toml").to_string())]
+        config_file: String,
+    },
+
+    /// Fix issues of the project files reported by the lint rules
+    Fix {
+        #[command(subcommand)]
+        command: FixCommands,
+    },
+
+    /// Generate the documentation of the project and dependency headers from their doc comments
+    Doc {
+        /// Maky config file or folder
+        #[arg(short = 'f', long = "file", default_value_t = ("./Maky.toml").to_string())]
+        config_file: String,
+    },
+
+    /// Inspect the project config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
     },
 
     /// Remove artifacts generated by Maky in the past
@@ -87,55 +179,208 @@ enum Commands {
     },
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+#[derive(Subcommand, Debug)]
+enum FixCommands {
+    /// Add the missing include guards of the headers and rename the mismatched ones
+    Guards {
+        /// Maky config file or folder
+        #[arg(short = 'f', long = "file", default_value_t = ("./Maky.toml").to_string())]
+        config_file: String,
+
+        /// Write nothing, print a diff of the headers that would change and exit with an error if any would
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Print the config used to build, once the specific configs are merged, with where each value comes from
+    Show {
+        /// Maky config file or folder
+        #[arg(short = 'f', long = "file", default_value_t = ("./Maky.toml").to_string())]
+        config_file: String,
+
+        /// Target triple to resolve the config for, like `aarch64-unknown-linux-gnu`, instead of the host
+        #[arg(long)]
+        target: Option<String>,
+
+        /// CPU to resolve the config for, like `native` or `x86-64-v3`
+        #[arg(long)]
+        target_cpu: Option<String>,
+
+        /// C compiler, before the `CC` environment variable, the specific configs and the package
+        #[arg(long)]
+        cc: Option<String>,
+
+        /// C++ compiler, before the `CXX` environment variable, the specific configs and the package
+        #[arg(long)]
+        cxx: Option<String>,
+
+        /// CPU features to enable like `+avx2` or disable like `-avx2`, separated by commas
+        #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+        target_feature: Vec<String>,
+
+        /// Features of the `[features]` table to enable, separated by commas
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+
+        /// Don't enable the `default` features
+        #[arg(long)]
+        no_default_features: bool,
+
+        /// Profile of the flags
+        #[arg(long, value_enum, default_value_t = Profile::Dev)]
+        profile: Profile,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ConfigFormat::Toml)]
+        format: ConfigFormat,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
     kdam::term::init(true);
 
     let args = Args::parse();
 
     if let Some(command) = args.command {
-        match command {
-            Commands::Init { path } => init(path)?,
-            Commands::Build {
-                config_file,
-                release,
-                rebuild,
-                pretty,
-            } => {
-                build(
-                    config_file,
-                    &BuildFlags {
-                        release,
-                        rebuild,
-                        pretty,
-                    },
-                    &mut stderr(),
-                )?;
+        // Config problems are reported with their location, then fail the command
+        if let Err(error) = run_command(command) {
+            match error.downcast::<ConfigError>() {
+                Ok(error) => {
+                    ProjectConfig::handle_error(error)?;
+                    exit(1);
+                }
+                Err(error) => return Err(error),
             }
-            Commands::Run {
-                config_file,
-                release,
-                rebuild,
-                file,
-                args,
-            } => run(config_file, release, rebuild, file, args)?,
-            Commands::Fmt {
-                files,
+        }
+    }
+
+    Ok(())
+}
+
+fn run_command(command: Commands) -> anyhow::Result<()> {
+    match command {
+        Commands::Init { path } => init(path)?,
+        Commands::Build {
+            config_file,
+            release,
+            rebuild,
+            pretty,
+            target,
+            target_cpu,
+            target_feature,
+            features,
+            no_default_features,
+            cc,
+            cxx,
+        } => {
+            build(
                 config_file,
+                &BuildFlags {
+                    release,
+                    target,
+                    c_compiler: cc,
+                    cpp_compiler: cxx,
+                    rebuild,
+                    pretty,
+                    target_cpu,
+                    target_features: target_feature,
+                    features,
+                    no_default_features,
+                },
+                &mut stderr(),
+            )?;
+        }
+        Commands::Run {
+            config_file,
+            release,
+            rebuild,
+            file,
+            args,
+        } => run(config_file, release, rebuild, file, args)?,
+        Commands::Fmt {
+            files,
+            config_file,
+            tab_size,
+            max_width,
+            sort_includes,
+            changed,
+            base,
+            stdin,
+            assume_filename,
+            check,
+            diff,
+        } => {
+            let format_config = FormatConfig {
                 tab_size,
-            } => {
-                format(
+                max_width,
+                sort_includes: sort_includes.then_some(true),
+                ..Default::default()
+            };
+
+            if stdin {
+                if format_stdin(assume_filename, config_file, format_config)? {
+                    exit(1);
+                }
+            } else {
+                let is_unformatted = format(
                     files,
                     config_file,
-                    &FormatOptions {
-                        tab: " ".repeat(tab_size).to_string(),
-                    },
-                )
-                .await?
+                    format_config,
+                    changed.then_some(base),
+                    &FormatFlags { check, diff },
+                )?;
+
+                if check && is_unformatted {
+                    exit(1);
+                }
             }
-            Commands::Clean { config_file } => clean(config_file)?,
         }
+        Commands::Lint { files, config_file } => {
+            if lint(files, config_file)? {
+                exit(1);
+            }
+        }
+        Commands::Fix { command } => match command {
+            FixCommands::Guards { config_file, check } => {
+                if fix_guards(config_file, check)? && check {
+                    exit(1);
+                }
+            }
+        },
+        Commands::Doc { config_file } => doc(config_file)?,
+        Commands::Config { command } => match command {
+            ConfigCommands::Show {
+                config_file,
+                target,
+                target_cpu,
+                target_feature,
+                features,
+                no_default_features,
+                cc,
+                cxx,
+                profile,
+                format,
+            } => config_show(
+                config_file,
+                &BuildFlags {
+                    release: profile == Profile::Release,
+                    target,
+                    c_compiler: cc,
+                    cpp_compiler: cxx,
+                    target_cpu,
+                    target_features: target_feature,
+                    features,
+                    no_default_features,
+                    ..Default::default()
+                },
+                format,
+            )?,
+        },
+        Commands::Clean { config_file } => clean(config_file)?,
     }
 
-    return Ok(());
+    Ok(())
 }
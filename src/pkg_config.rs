@@ -6,10 +6,10 @@ pub trait ParsePkgVersion {
 
 impl ParsePkgVersion for Config {
     fn parse_version(&mut self, pkg_version: &str) -> &mut Self {
-        if pkg_version.starts_with("=") {
-            self.exactly_version(&pkg_version[1..]);
-        } else if pkg_version.starts_with(">=") {
-            self.atleast_version(&pkg_version[2..]);
+        if let Some(pkg_version) = pkg_version.strip_prefix("=") {
+            self.exactly_version(pkg_version);
+        } else if let Some(pkg_version) = pkg_version.strip_prefix(">=") {
+            self.atleast_version(pkg_version);
         } else if pkg_version.contains("..") {
             let pkg_version_split: Vec<&str> = pkg_version.split("..").collect();
 